@@ -0,0 +1,55 @@
+//! Allocations made while building typical diagnostics.
+//!
+//! Compare `cargo bench --bench allocations` with
+//! `cargo bench --bench allocations --features smallvec`.
+
+use proc_macro2::Span;
+use proc_macro_error::*;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+fn count(name: &str, f: impl FnOnce() -> Diagnostic) {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    let diag = f();
+    let after = ALLOCATIONS.load(Ordering::Relaxed);
+    drop(diag);
+    println!("{}: {} allocations", name, after - before);
+}
+
+fn main() {
+    let span = Span::call_site();
+
+    count("no notes", || {
+        Diagnostic::spanned(span, Level::Error, "error".into())
+    });
+    count("one note", || {
+        Diagnostic::spanned(span, Level::Error, "error".into()).note("note".into())
+    });
+    count("two notes", || {
+        Diagnostic::spanned(span, Level::Error, "error".into())
+            .note("note".into())
+            .help("help".into())
+    });
+    count("one child", || {
+        Diagnostic::spanned(span, Level::Error, "error".into()).span_error(span, "child".into())
+    });
+}
@@ -1,5 +1,14 @@
+use std::env;
+
 fn main() {
-    if !version_check::is_feature_flaggable().unwrap_or(false) {
+    // declare the custom cfgs, newer compilers warn about unknown ones
+    println!("cargo:rustc-check-cfg=cfg(use_fallback)");
+    println!("cargo:rustc-check-cfg=cfg(skip_ui_tests)");
+    println!("cargo:rustc-check-cfg=cfg(format_lone_literal)");
+
+    if env::var_os("CARGO_FEATURE_FORCE_FALLBACK").is_some()
+        || !version_check::is_feature_flaggable().unwrap_or(false)
+    {
         println!("cargo:rustc-cfg=use_fallback");
     }
 
@@ -8,4 +17,9 @@ fn main() {
     {
         println!("cargo:rustc-cfg=skip_ui_tests");
     }
+
+    // the `literal` macro fragment is needed to tell a lone literal apart
+    if version_check::is_min_version("1.32.0").unwrap_or(false) {
+        println!("cargo:rustc-cfg=format_lone_literal");
+    }
 }
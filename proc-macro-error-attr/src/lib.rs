@@ -85,14 +85,15 @@ fn impl_proc_macro_error(attr: TokenStream2, input: TokenStream2) -> Result<Toke
 
 #[cfg(not(always_assert_unwind))]
 fn gen_body(block: TokenTree, settings: Settings) -> proc_macro2::TokenStream {
-    let is_proc_macro_hack = settings.is_set(ProcMacroHack);
     let closure = if settings.is_set(AssertUnwindSafe) {
         quote!(::std::panic::AssertUnwindSafe(|| #block ))
     } else {
         quote!(|| #block)
     };
+    let krate = settings.crate_path();
+    let settings = gen_entry_point_settings(&settings);
 
-    quote!( ::proc_macro_error::entry_point(#closure, #is_proc_macro_hack) )
+    quote!( #krate::entry_point(#closure, #settings) )
 }
 
 // FIXME:
@@ -101,21 +102,50 @@ fn gen_body(block: TokenTree, settings: Settings) -> proc_macro2::TokenStream {
 // for virtually every closure possible, the check is meaningless.
 #[cfg(always_assert_unwind)]
 fn gen_body(block: TokenTree, settings: Settings) -> proc_macro2::TokenStream {
-    let is_proc_macro_hack = settings.is_set(ProcMacroHack);
     let closure = quote!(::std::panic::AssertUnwindSafe(|| #block ));
-    quote!( ::proc_macro_error::entry_point(#closure, #is_proc_macro_hack) )
+    let krate = settings.crate_path();
+    let settings = gen_entry_point_settings(&settings);
+    quote!( #krate::entry_point(#closure, #settings) )
+}
+
+fn gen_entry_point_settings(settings: &Settings) -> proc_macro2::TokenStream {
+    let krate = settings.crate_path();
+    let proc_macro_hack = settings.is_set(ProcMacroHack);
+    let dedup = settings.is_set(Dedup);
+    let warn_as_error = settings.is_set(WarnAsError);
+    let expr_context = settings.is_set(ExprContext);
+    let single_line = settings.is_set(SingleLine);
+    let summary = settings.is_set(Summary);
+    let emit_on_panic = settings.is_set(EmitOnPanic);
+    let max_errors = match settings.max_errors() {
+        Some(max) => quote!(::std::option::Option::Some(#max)),
+        None => quote!(::std::option::Option::None),
+    };
+
+    quote! {
+        #krate::EntryPointSettings {
+            proc_macro_hack: #proc_macro_hack,
+            dedup: #dedup,
+            warn_as_error: #warn_as_error,
+            expr_context: #expr_context,
+            single_line: #single_line,
+            summary: #summary,
+            emit_on_panic: #emit_on_panic,
+            max_errors: #max_errors,
+        }
+    }
 }
 
 fn detect_proc_macro_hack(attrs: &[Attribute]) -> bool {
     attrs
         .iter()
-        .any(|attr| attr.path_is_ident("proc_macro_hack"))
+        .any(|attr| attr.path_ends_with("proc_macro_hack"))
 }
 
 fn is_proc_macro(attrs: &[Attribute]) -> bool {
     attrs.iter().any(|attr| {
-        attr.path_is_ident("proc_macro")
-            || attr.path_is_ident("proc_macro_derive")
-            || attr.path_is_ident("proc_macro_attribute")
+        attr.path_ends_with("proc_macro")
+            || attr.path_ends_with("proc_macro_derive")
+            || attr.path_ends_with("proc_macro_attribute")
     })
 }
@@ -44,15 +44,21 @@ fn parse_next_attr(
         }
     };
 
-    let path = match group.stream().into_iter().next() {
-        Some(TokenTree::Ident(ident)) => Some(ident),
-        _ => None,
-    };
+    // `a::b::c(...)` or `::a::b = ...`, the path ends at the first token
+    // that is neither an identifier nor a `:`
+    let mut last_segment = None;
+    for tt in group.stream() {
+        match tt {
+            TokenTree::Ident(ident) => last_segment = Some(ident),
+            TokenTree::Punct(ref punct) if punct.as_char() == ':' => {}
+            _ => break,
+        }
+    }
 
     Ok(Some(Attribute {
         shebang,
         group: TokenTree::Group(group),
-        path,
+        last_segment,
     }))
 }
 
@@ -72,12 +78,14 @@ fn parse_signature(input: &mut Peekable<impl Iterator<Item = TokenTree>>) -> Vec
 pub(crate) struct Attribute {
     pub(crate) shebang: TokenTree,
     pub(crate) group: TokenTree,
-    pub(crate) path: Option<Ident>,
+    pub(crate) last_segment: Option<Ident>,
 }
 
 impl Attribute {
-    pub(crate) fn path_is_ident(&self, ident: &str) -> bool {
-        self.path.as_ref().map_or(false, |p| *p == ident)
+    /// Whether the last segment of the path is `ident`, e.g. both
+    /// `#[foo]` and `#[bar::foo]` end with `foo`.
+    pub(crate) fn path_ends_with(&self, ident: &str) -> bool {
+        self.last_segment.as_ref().map_or(false, |p| *p == ident)
     }
 }
 
@@ -1,5 +1,7 @@
 use crate::{Error, Result};
 use proc_macro2::{Ident, Span, TokenStream, TokenTree};
+use quote::quote;
+use std::iter::Peekable;
 
 macro_rules! decl_settings {
     ($($val:expr => $variant:ident),+ $(,)*) => {
@@ -12,7 +14,8 @@ macro_rules! decl_settings {
             match &*ident.to_string() {
                 $($val => Ok(Setting::$variant),)*
                 _ => {
-                    let possible_vals = [$($val),*]
+                    // `crate` and `max_errors` take a value and are parsed separately
+                    let possible_vals = [$($val,)* "crate = path", "max_errors = N"]
                         .iter()
                         .map(|v| format!("`{}`", v))
                         .collect::<Vec<_>>()
@@ -31,15 +34,41 @@ decl_settings! {
     "assert_unwind_safe" => AssertUnwindSafe,
     "allow_not_macro"    => AllowNotMacro,
     "proc_macro_hack"    => ProcMacroHack,
+    "dedup"              => Dedup,
+    "warn_as_error"      => WarnAsError,
+    "expr_context"       => ExprContext,
+    "single_line"        => SingleLine,
+    "summary"            => Summary,
+    "emit_on_panic"      => EmitOnPanic,
 }
 
 pub(crate) fn parse_settings(input: TokenStream) -> Result<Settings> {
-    let mut input = input.into_iter();
-    let mut res = Settings(Vec::new());
+    let mut input = input.into_iter().peekable();
+    let mut res = Settings {
+        settings: Vec::new(),
+        crate_path: None,
+        max_errors: None,
+    };
     loop {
         match input.next() {
+            Some(TokenTree::Ident(ref ident)) if ident == "crate" => {
+                if res.crate_path.is_some() {
+                    return Err(duplicate_setting(ident));
+                }
+                res.crate_path = Some(parse_crate_path(ident, &mut input)?);
+            }
+            Some(TokenTree::Ident(ref ident)) if ident == "max_errors" => {
+                if res.max_errors.is_some() {
+                    return Err(duplicate_setting(ident));
+                }
+                res.max_errors = Some(parse_max_errors(ident, &mut input)?);
+            }
             Some(TokenTree::Ident(ident)) => {
-                res.0.push(ident_to_setting(ident)?);
+                let setting = ident_to_setting(ident.clone())?;
+                if res.settings.contains(&setting) {
+                    return Err(duplicate_setting(&ident));
+                }
+                res.settings.push(setting);
             }
             None => return Ok(res),
             other => {
@@ -59,14 +88,96 @@ pub(crate) fn parse_settings(input: TokenStream) -> Result<Settings> {
     }
 }
 
-pub(crate) struct Settings(Vec<Setting>);
+fn duplicate_setting(ident: &Ident) -> Error {
+    Error::new(ident.span(), format!("duplicate setting `{}`", ident))
+}
+
+// crate = path::to::proc_macro_error
+fn parse_crate_path(
+    ident: &Ident,
+    input: &mut Peekable<impl Iterator<Item = TokenTree>>,
+) -> Result<TokenStream> {
+    match input.next() {
+        Some(TokenTree::Punct(ref punct)) if punct.as_char() == '=' => {}
+        other => {
+            let span = other.map_or(Span::call_site(), |tt| tt.span());
+            return Err(Error::new(span, "expected `=`".to_string()));
+        }
+    }
+
+    let mut path = TokenStream::new();
+    loop {
+        match input.peek() {
+            Some(TokenTree::Punct(ref punct)) if punct.as_char() == ',' => break,
+            None => break,
+            _ => path.extend(input.next()),
+        }
+    }
+
+    if path.is_empty() {
+        return Err(Error::new(
+            ident.span(),
+            "expected path after `crate =`".to_string(),
+        ));
+    }
+
+    Ok(path)
+}
+
+// max_errors = 20
+fn parse_max_errors(
+    ident: &Ident,
+    input: &mut Peekable<impl Iterator<Item = TokenTree>>,
+) -> Result<usize> {
+    match input.next() {
+        Some(TokenTree::Punct(ref punct)) if punct.as_char() == '=' => {}
+        other => {
+            let span = other.map_or(Span::call_site(), |tt| tt.span());
+            return Err(Error::new(span, "expected `=`".to_string()));
+        }
+    }
+
+    match input.next() {
+        Some(TokenTree::Literal(lit)) => match lit.to_string().parse() {
+            Ok(0) | Err(_) => Err(Error::new(
+                lit.span(),
+                "expected positive integer".to_string(),
+            )),
+            Ok(max) => Ok(max),
+        },
+        other => {
+            let span = other.map_or(ident.span(), |tt| tt.span());
+            Err(Error::new(span, "expected positive integer".to_string()))
+        }
+    }
+}
+
+pub(crate) struct Settings {
+    settings: Vec<Setting>,
+    crate_path: Option<TokenStream>,
+    max_errors: Option<usize>,
+}
 
 impl Settings {
     pub(crate) fn is_set(&self, setting: Setting) -> bool {
-        self.0.iter().any(|s| *s == setting)
+        self.settings.iter().any(|s| *s == setting)
     }
 
     pub(crate) fn set(&mut self, setting: Setting) {
-        self.0.push(setting)
+        self.settings.push(setting)
+    }
+
+    /// The `max_errors = N` limit, if specified.
+    pub(crate) fn max_errors(&self) -> Option<usize> {
+        self.max_errors
+    }
+
+    /// Path to `proc_macro_error` crate, `::proc_macro_error` unless
+    /// `crate = ...` was specified.
+    pub(crate) fn crate_path(&self) -> TokenStream {
+        match self.crate_path {
+            Some(ref path) => path.clone(),
+            None => quote!(::proc_macro_error),
+        }
     }
 }
@@ -1,30 +1,136 @@
-use crate::{abort_now, check_correctness, sealed::Sealed, SpanRange};
+use crate::{
+    abort_now, call_emit_hook, check_correctness, is_duplicate, note_prefix, sealed::Sealed,
+    single_line, warn_as_error, SpanRange,
+};
 use proc_macro2::Span;
 use proc_macro2::TokenStream;
 
 use quote::{quote_spanned, ToTokens};
 
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
 /// Represents a diagnostic level
 ///
 /// # Warnings
 ///
-/// Warnings are ignored on stable/beta
-#[derive(Debug, PartialEq)]
+/// Warnings and notes are ignored on stable/beta
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Level {
     Error,
     Warning,
+    /// A standalone note, e.g. an explanation of what the macro generated.
+    /// Just like a warning, it doesn't fail the compilation.
+    Note,
+    /// An error with a custom, domain-specific label like "lint" or "style".
+    ///
+    /// It's an error as far as the compiler is concerned, the label is
+    /// shown in front of the message: `error: lint: message`.
+    Custom(String),
     #[doc(hidden)]
     NonExhaustive,
 }
 
+impl Level {
+    fn name(&self) -> &str {
+        match self {
+            Level::Error => "error",
+            Level::Warning => "warning",
+            Level::Note => "note",
+            Level::Custom(label) => label,
+            Level::NonExhaustive => "nonexhaustive",
+        }
+    }
+
+    /// The ANSI escape code of the conventional color of the level:
+    /// bold red for errors (custom levels included), bold yellow for warnings,
+    /// bold green for notes.
+    #[cfg(feature = "color")]
+    pub fn color(&self) -> &'static str {
+        match self {
+            Level::Error | Level::Custom(_) => "\x1b[1;31m",
+            Level::Warning => "\x1b[1;33m",
+            Level::Note => "\x1b[1;32m",
+            Level::NonExhaustive => "",
+        }
+    }
+
+    /// Whether the compilation fails because of this level.
+    pub(crate) fn is_error(&self) -> bool {
+        match self {
+            Level::Error | Level::Custom(_) | Level::NonExhaustive => true,
+            Level::Warning | Level::Note => false,
+        }
+    }
+}
+
+/// Yields the label the level is rendered with: `error`, `warning`, `note`
+/// or the custom label.
+impl Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Parses `"error"`, `"warning"` and `"note"`. Custom levels are never produced,
+/// build them explicitly via [`Level::Custom`].
+///
+/// [`Level::Custom`]: enum.Level.html#variant.Custom
+impl FromStr for Level {
+    type Err = ParseLevelError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(Level::Error),
+            "warning" => Ok(Level::Warning),
+            "note" => Ok(Level::Note),
+            _ => Err(ParseLevelError(s.to_string())),
+        }
+    }
+}
+
+/// An error returned when parsing a [`Level`] fails.
+///
+/// [`Level`]: enum.Level.html
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseLevelError(String);
+
+impl Display for ParseLevelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "unknown diagnostic level `{}`, expected `error`, `warning` or `note`",
+            self.0
+        )
+    }
+}
+
+impl Error for ParseLevelError {}
+
+// Most diagnostics have no more than a couple of notes,
+// `smallvec` saves the allocations for them.
+#[cfg(feature = "smallvec")]
+pub(crate) type Attachments<T> = smallvec::SmallVec<[T; 2]>;
+#[cfg(not(feature = "smallvec"))]
+pub(crate) type Attachments<T> = Vec<T>;
+
 /// Represents a single diagnostic message
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Diagnostic {
     pub(crate) level: Level,
     pub(crate) span_range: SpanRange,
     pub(crate) msg: String,
-    pub(crate) suggestions: Vec<(SuggestionKind, String, Option<SpanRange>)>,
-    pub(crate) children: Vec<(SpanRange, String)>,
+    pub(crate) suggestions: Attachments<(SuggestionKind, String, Option<SpanRange>)>,
+    // `Vec` because `SmallVec` would make the type infinitely sized
+    pub(crate) children: Vec<Diagnostic>,
+    pub(crate) code: Option<String>,
+    pub(crate) spanned_notes_as_errors: bool,
+    pub(crate) unspanned: bool,
+    #[cfg(feature = "debug-backtrace")]
+    pub(crate) backtrace: Option<String>,
 }
 
 /// A collection of methods that do not exist in `proc_macro::Diagnostic`
@@ -45,6 +151,17 @@ pub trait DiagnosticExt: Sealed {
     /// better error messages for multi-token spans on stable.
     fn span_range_error(self, span_range: SpanRange, msg: String) -> Self;
 
+    /// Add a warning to self such that it will be emitted right after
+    /// the main message.
+    ///
+    /// This function is the same as `Diagnostic::span_warning` but produces considerably
+    /// better error messages for multi-token spans on stable.
+    ///
+    /// # Warnings
+    ///
+    /// Warnings are ignored on stable/beta
+    fn span_range_warning(self, span_range: SpanRange, msg: String) -> Self;
+
     /// Attach a "help" note to your main message, the note will have it's own span on nightly.
     ///
     /// This function is the same as `Diagnostic::span_help` but produces considerably
@@ -52,7 +169,8 @@ pub trait DiagnosticExt: Sealed {
     ///
     /// # Span
     ///
-    /// The span is ignored on stable, the note effectively inherits its parent's (main message) span
+    /// The span is ignored on stable, the note effectively inherits its parent's (main message) span.
+    /// The source text the span points to is appended to the note instead: ``note (at `text`)``.
     fn span_range_help(self, span_range: SpanRange, msg: String) -> Self;
 
     /// Attach a note to your main message, the note will have it's own span on nightly.
@@ -62,24 +180,22 @@ pub trait DiagnosticExt: Sealed {
     ///
     /// # Span
     ///
-    /// The span is ignored on stable, the note effectively inherits its parent's (main message) span
+    /// The span is ignored on stable, the note effectively inherits its parent's (main message) span.
+    /// The source text the span points to is appended to the note instead: ``note (at `text`)``.
     fn span_range_note(self, span_range: SpanRange, msg: String) -> Self;
 }
 
 impl DiagnosticExt for Diagnostic {
     fn spanned_range(span_range: SpanRange, level: Level, message: String) -> Self {
-        Diagnostic {
-            level,
-            span_range,
-            msg: message,
-            suggestions: vec![],
-            children: vec![],
-        }
+        Diagnostic::spanned_range(span_range, level, message)
     }
 
-    fn span_range_error(mut self, span_range: SpanRange, msg: String) -> Self {
-        self.children.push((span_range, msg));
-        self
+    fn span_range_error(self, span_range: SpanRange, msg: String) -> Self {
+        self.child(Diagnostic::spanned_range(span_range, Level::Error, msg))
+    }
+
+    fn span_range_warning(self, span_range: SpanRange, msg: String) -> Self {
+        self.child(Diagnostic::spanned_range(span_range, Level::Warning, msg))
     }
 
     fn span_range_help(mut self, span_range: SpanRange, msg: String) -> Self {
@@ -101,7 +217,26 @@ impl Diagnostic {
         Diagnostic::spanned(Span::call_site(), level, message)
     }
 
-    /// Create a new diagnostic message that points to the `span`
+    /// Create a new diagnostic message with no specific location, for errors not tied
+    /// to any input token, e.g. "this macro requires the `foo` feature".
+    ///
+    /// # Span
+    ///
+    /// On nightly, the diagnostic is reported without a span. On stable, `compile_error!`
+    /// must have one, so it points to `Span::call_site()` like [`Diagnostic::new`].
+    ///
+    /// [`Diagnostic::new`]: struct.Diagnostic.html#method.new
+    pub fn unspanned(level: Level, message: String) -> Self {
+        let mut res = Diagnostic::new(level, message);
+        res.unspanned = true;
+        res
+    }
+
+    /// Create a new diagnostic message that points to the `span`.
+    ///
+    /// See [`Diagnostic::spanned_range`] if you have a `proc_macro::Span`.
+    ///
+    /// [`Diagnostic::spanned_range`]: struct.Diagnostic.html#method.spanned_range
     pub fn spanned(span: Span, level: Level, message: String) -> Self {
         Diagnostic::spanned_range(
             SpanRange {
@@ -113,6 +248,34 @@ impl Diagnostic {
         )
     }
 
+    /// Create a new diagnostic message that underlines the whole `range`,
+    /// from the start of `range.first` to the end of `range.last`.
+    ///
+    /// Unlike [`Diagnostic::spanned`], this preserves multi-token spans on stable.
+    /// Available without importing [`DiagnosticExt`].
+    ///
+    /// Accepts anything that converts into [`SpanRange`]: a `proc_macro::Span`
+    /// or `proc_macro2::Span` (single span), a `(first, last)` pair of spans
+    /// or the range itself.
+    ///
+    /// [`Diagnostic::spanned`]: struct.Diagnostic.html#method.spanned
+    /// [`DiagnosticExt`]: trait.DiagnosticExt.html
+    /// [`SpanRange`]: struct.SpanRange.html
+    pub fn spanned_range(range: impl Into<SpanRange>, level: Level, message: String) -> Self {
+        Diagnostic {
+            level,
+            span_range: range.into(),
+            msg: message,
+            suggestions: Attachments::new(),
+            children: Vec::new(),
+            code: None,
+            spanned_notes_as_errors: false,
+            unspanned: false,
+            #[cfg(feature = "debug-backtrace")]
+            backtrace: capture_backtrace(),
+        }
+    }
+
     /// Add another error message to self such that it will be emitted right after
     /// the main message.
     pub fn span_error(self, span: Span, msg: String) -> Self {
@@ -125,11 +288,22 @@ impl Diagnostic {
         )
     }
 
+    /// Add a warning to self such that it will be emitted right after
+    /// the main message.
+    ///
+    /// # Warnings
+    ///
+    /// Warnings are ignored on stable/beta
+    pub fn span_warning(self, span: Span, msg: String) -> Self {
+        self.span_range_warning(SpanRange::single_span(span), msg)
+    }
+
     /// Attach a "help" note to your main message, the note will have it's own span on nightly.
     ///
     /// # Span
     ///
-    /// The span is ignored on stable, the note effectively inherits its parent's (main message) span
+    /// The span is ignored on stable, the note effectively inherits its parent's (main message) span.
+    /// The source text the span points to is appended to the note instead: ``note (at `text`)``.
     pub fn span_help(self, span: Span, msg: String) -> Self {
         self.span_range_help(
             SpanRange {
@@ -150,7 +324,8 @@ impl Diagnostic {
     ///
     /// # Span
     ///
-    /// The span is ignored on stable, the note effectively inherits its parent's (main message) span
+    /// The span is ignored on stable, the note effectively inherits its parent's (main message) span.
+    /// The source text the span points to is appended to the note instead: ``note (at `text`)``.
     pub fn span_note(self, span: Span, msg: String) -> Self {
         self.span_range_note(
             SpanRange {
@@ -161,17 +336,388 @@ impl Diagnostic {
         )
     }
 
+    /// Point to the other side of a conflict, e.g. "first defined here".
+    ///
+    /// # Span
+    ///
+    /// On nightly, this is a spanned note. On stable, spanned notes lose their spans,
+    /// so a separate error pointing to `other` is added instead: this way both locations
+    /// are underlined.
+    pub fn span_conflict(self, other: Span, msg: String) -> Self {
+        if cfg!(use_fallback) {
+            self.span_error(other, msg)
+        } else {
+            self.span_note(other, msg)
+        }
+    }
+
     /// Attach a note to your main message
     pub fn note(mut self, msg: String) -> Self {
         self.suggestions.push((SuggestionKind::Note, msg, None));
         self
     }
 
+    /// Attach a multi-line "help" note to your main message.
+    ///
+    /// Unlike [`Diagnostic::help`], continuation lines of `msg` are indented so they
+    /// line up with the first one, right after the `= help: ` prefix.
+    ///
+    /// [`Diagnostic::help`]: struct.Diagnostic.html#method.help
+    pub fn help_indented(mut self, msg: String) -> Self {
+        self.suggestions
+            .push((SuggestionKind::IndentedHelp, msg, None));
+        self
+    }
+
+    /// Attach a multi-line note to your main message.
+    ///
+    /// Unlike [`Diagnostic::note`], continuation lines of `msg` are indented so they
+    /// line up with the first one, right after the `= note: ` prefix.
+    ///
+    /// [`Diagnostic::note`]: struct.Diagnostic.html#method.note
+    pub fn note_indented(mut self, msg: String) -> Self {
+        self.suggestions
+            .push((SuggestionKind::IndentedNote, msg, None));
+        self
+    }
+
+    /// Attach a "help" note to your main message if `cond` is `true`.
+    ///
+    /// This is the builder counterpart of the `help =? expr` syntax of the macros.
+    pub fn help_if(self, cond: bool, msg: String) -> Self {
+        if cond {
+            self.help(msg)
+        } else {
+            self
+        }
+    }
+
+    /// Attach a note to your main message if `cond` is `true`.
+    ///
+    /// This is the builder counterpart of the `note =? expr` syntax of the macros.
+    pub fn note_if(self, cond: bool, msg: String) -> Self {
+        if cond {
+            self.note(msg)
+        } else {
+            self
+        }
+    }
+
+    /// On stable, emit every note and help message that has its own span as
+    /// a separate `compile_error!` pointing to that span, instead of folding it
+    /// into the main message. This way the user at least sees the location.
+    ///
+    /// Off by default since it produces extra errors (`error: help: ...`),
+    /// has no effect on nightly where the spans are displayed properly.
+    pub fn spanned_notes_as_errors(mut self) -> Self {
+        self.spanned_notes_as_errors = true;
+        self
+    }
+
+    /// Point the main message to another range. Notes and children keep their spans.
+    ///
+    /// Together with `Clone`, this allows to build a diagnostic once and
+    /// stamp it at every offending location.
+    pub fn set_spans(&mut self, range: SpanRange) {
+        self.span_range = range;
+        self.unspanned = false;
+    }
+
+    /// Chainable version of [`Diagnostic::set_spans`].
+    ///
+    /// [`Diagnostic::set_spans`]: struct.Diagnostic.html#method.set_spans
+    pub fn with_spans(mut self, range: SpanRange) -> Self {
+        self.set_spans(range);
+        self
+    }
+
+    /// Underline exactly the tokens of `node`, from its first token to the last one.
+    ///
+    /// This is what [`abort!`] and [`emit_error!`] do when given a `ToTokens`
+    /// value as the span, and it gives the best underlines on stable: a single
+    /// `Span` of a multi-token node would point to its first token only.
+    ///
+    /// ```ignore
+    /// Diagnostic::new(Level::Error, "unsupported field type".into())
+    ///     .with_spans_from(&field.ty)
+    ///     .emit();
+    /// ```
+    ///
+    /// An empty `node` resolves at call site.
+    ///
+    /// [`abort!`]: macro.abort.html
+    /// [`emit_error!`]: macro.emit_error.html
+    pub fn with_spans_from<T: ToTokens>(self, node: &T) -> Self {
+        self.with_spans(SpanRange::from_tokens(node))
+    }
+
+    /// Insert `prefix` at the beginning of the main message, as is.
+    ///
+    /// Useful to add context to an error bubbling up from a sub-parser:
+    /// `err.prepend_message("while parsing attributes: ")`.
+    pub fn prepend_message(&mut self, prefix: &str) {
+        self.msg.insert_str(0, prefix);
+    }
+
+    /// Append `suffix` to the end of the main message, as is.
+    pub fn append_message(&mut self, suffix: &str) {
+        self.msg.push_str(suffix);
+    }
+
+    /// Run the main message through `f`, e.g. to localize it or to prefix
+    /// all the messages collected from a sub-module in one pass.
+    /// Notes and children are left as is.
+    pub fn map_message<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(String) -> String,
+    {
+        self.msg = f(self.msg);
+        self
+    }
+
+    /// Change the level of the diagnostic, e.g. to turn an error into a warning
+    /// depending on a "deny/warn" setting of your macro.
+    pub fn set_level(&mut self, level: Level) {
+        self.level = level;
+    }
+
+    /// Chainable version of [`Diagnostic::set_level`].
+    ///
+    /// [`Diagnostic::set_level`]: struct.Diagnostic.html#method.set_level
+    pub fn with_level(mut self, level: Level) -> Self {
+        self.set_level(level);
+        self
+    }
+
+    /// Attach an error code to the diagnostic, like `E0599` in `rustc` diagnostics.
+    ///
+    /// # Rendering
+    ///
+    /// `proc_macro::Diagnostic` has no notion of error codes (yet), so the main
+    /// message is prefixed with `[CODE]` on both stable and nightly.
+    pub fn code(mut self, code: impl Into<String>) -> Self {
+        self.code = Some(code.into());
+        self
+    }
+
+    /// Suggest replacing the code at `span` with `replacement`.
+    ///
+    /// # Rendering
+    ///
+    /// `proc_macro::Diagnostic` doesn't support structured suggestions (yet),
+    /// so this is rendered as a "help" note with the replacement inlined
+    /// (`help: msg: `replacement``) on both stable and nightly.
+    ///
+    /// # Span
+    ///
+    /// The span is ignored on stable, the note effectively inherits its parent's (main message) span.
+    /// The source text the span points to is appended to the note instead: ``note (at `text`)``.
+    pub fn span_suggestion_replacement(
+        mut self,
+        span: Span,
+        msg: String,
+        replacement: String,
+    ) -> Self {
+        self.suggestions.push((
+            SuggestionKind::Replacement(replacement),
+            msg,
+            Some(SpanRange::single_span(span)),
+        ));
+        self
+    }
+
+    /// Same as [`Diagnostic::new`] but accepts anything that implements `Display`,
+    /// so you don't need to call `.to_string()` yourself.
+    ///
+    /// [`Diagnostic::new`]: struct.Diagnostic.html#method.new
+    pub fn new_display(level: Level, message: impl Display) -> Self {
+        Diagnostic::new(level, message.to_string())
+    }
+
+    /// Same as [`Diagnostic::spanned`] but accepts anything that implements `Display`.
+    ///
+    /// [`Diagnostic::spanned`]: struct.Diagnostic.html#method.spanned
+    pub fn spanned_display(span: Span, level: Level, message: impl Display) -> Self {
+        Diagnostic::spanned(span, level, message.to_string())
+    }
+
+    /// Same as [`Diagnostic::span_error`] but accepts anything that implements `Display`.
+    ///
+    /// [`Diagnostic::span_error`]: struct.Diagnostic.html#method.span_error
+    pub fn span_error_display(self, span: Span, msg: impl Display) -> Self {
+        self.span_error(span, msg.to_string())
+    }
+
+    /// Same as [`Diagnostic::span_help`] but accepts anything that implements `Display`.
+    ///
+    /// [`Diagnostic::span_help`]: struct.Diagnostic.html#method.span_help
+    pub fn span_help_display(self, span: Span, msg: impl Display) -> Self {
+        self.span_help(span, msg.to_string())
+    }
+
+    /// Same as [`Diagnostic::help`] but accepts anything that implements `Display`.
+    ///
+    /// [`Diagnostic::help`]: struct.Diagnostic.html#method.help
+    pub fn help_display(self, msg: impl Display) -> Self {
+        self.help(msg.to_string())
+    }
+
+    /// Same as [`Diagnostic::span_note`] but accepts anything that implements `Display`.
+    ///
+    /// [`Diagnostic::span_note`]: struct.Diagnostic.html#method.span_note
+    pub fn span_note_display(self, span: Span, msg: impl Display) -> Self {
+        self.span_note(span, msg.to_string())
+    }
+
+    /// Same as [`Diagnostic::note`] but accepts anything that implements `Display`.
+    ///
+    /// [`Diagnostic::note`]: struct.Diagnostic.html#method.note
+    pub fn note_display(self, msg: impl Display) -> Self {
+        self.note(msg.to_string())
+    }
+
+    /// Attach the source code the diagnostic points to as a note,
+    /// so the diagnostic is self-contained when rendered or logged outside
+    /// of the compiler.
+    ///
+    /// The source text is available on nightly and on recent stable compilers,
+    /// if it can't be obtained this method does nothing.
+    pub fn with_source_context(self) -> Self {
+        match self.span_range.collapse().source_text() {
+            Some(text) => self.note(format!("source: `{}`", text)),
+            None => self,
+        }
+    }
+
     /// The message of main warning/error (no notes attached)
     pub fn message(&self) -> &str {
         &self.msg
     }
 
+    /// The level of the diagnostic.
+    pub fn level(&self) -> &Level {
+        &self.level
+    }
+
+    /// Whether the diagnostic fails the compilation: an error or a custom level.
+    pub fn is_error(&self) -> bool {
+        self.level.is_error()
+    }
+
+    /// Whether the diagnostic is a warning.
+    pub fn is_warning(&self) -> bool {
+        self.level == Level::Warning
+    }
+
+    /// Iterate over the child messages attached via `span_error` and alike
+    /// (or `syn::Error::combine`), yielding the first and the last spans
+    /// of the child along with its message.
+    pub fn children(&self) -> impl Iterator<Item = (Span, Span, &str)> {
+        self.children.iter().map(|child| {
+            (
+                child.span_range.first,
+                child.span_range.last,
+                child.msg.as_str(),
+            )
+        })
+    }
+
+    /// Attach a full-blown diagnostic as a child, with its own notes and children.
+    ///
+    /// `span_error` and `span_warning` are shortcuts for children without notes.
+    ///
+    /// # Rendering
+    ///
+    /// On nightly, `proc_macro::Diagnostic` children can't have notes of their own,
+    /// so the notes of the child are attached to the parent right after the child.
+    pub fn child(mut self, child: Diagnostic) -> Self {
+        self.children.push(child);
+        self
+    }
+
+    /// Nest the diagnostics caused by this one beneath it, e.g. the errors that
+    /// a malformed top-level attribute has led to.
+    ///
+    /// Unlike [`child`](#method.child), which adds co-equal parts of the same problem,
+    /// this models causality: every related diagnostic gets a `caused by: <message>`
+    /// note pointing back to `self`, so the relation is visible on stable too, where
+    /// each of them is a separate `compile_error!` following the header.
+    /// On nightly, they are subdiagnostics of `self`.
+    pub fn with_related(mut self, related: Vec<Diagnostic>) -> Self {
+        for diag in related {
+            let cause = format!("caused by: {}", self.msg);
+            self.children.push(diag.note(cause));
+        }
+        self
+    }
+
+    /// Remove the children identical to an earlier child (same level, span range,
+    /// message, notes and children), keeping the first one. Handy after merging
+    /// errors reported by several passes.
+    pub fn dedup_children(&mut self) {
+        let mut seen = HashSet::new();
+        self.children
+            .retain(|child| seen.insert(DiagnosticKey::new(child)));
+    }
+
+    /// Render the diagnostic in human-readable form, similar to `rustc`'s output:
+    ///
+    /// ```text
+    /// error[CODE]: main message
+    ///   = note: some note
+    ///   = help: some help
+    ///
+    /// error: child message
+    /// ```
+    ///
+    /// Spans are not rendered.
+    pub fn render(&self) -> String {
+        self.render_impl(false)
+    }
+
+    /// Same as [`render`](#method.render), but the levels are colored with
+    /// ANSI escape codes, see [`Level::color`]. For terminals of custom drivers,
+    /// `rustc` colors its output itself.
+    ///
+    /// [`Level::color`]: enum.Level.html#method.color
+    #[cfg(feature = "color")]
+    pub fn render_colored(&self) -> String {
+        self.render_impl(true)
+    }
+
+    fn render_impl(&self, colored: bool) -> String {
+        let mut res = String::new();
+
+        #[cfg(feature = "color")]
+        {
+            if colored {
+                res.push_str(self.level.color());
+            }
+        }
+        res.push_str(self.level.name());
+        if let Some(code) = &self.code {
+            res.push('[');
+            res.push_str(code);
+            res.push(']');
+        }
+        if colored {
+            res.push_str("\x1b[0m");
+        }
+        res.push_str(": ");
+        ensure_lf(&mut res, &self.msg);
+
+        for (kind, note, _span) in &self.suggestions {
+            push_suggestion(&mut res, kind, note);
+        }
+
+        for child in &self.children {
+            res.push('\n');
+            res.push_str(&child.render_impl(colored));
+        }
+
+        res
+    }
+
     /// Abort the proc-macro's execution and display the diagnostic.
     ///
     /// # Warnings
@@ -182,15 +728,87 @@ impl Diagnostic {
         abort_now()
     }
 
+    /// Display the diagnostic and abort the proc-macro's execution if `cond` is `true`,
+    /// otherwise continue.
+    ///
+    /// Handy for "collect the warnings but stop on the first error of this kind"
+    /// validation flows:
+    ///
+    /// ```ignore
+    /// for field in fields {
+    ///     if let Some(diag) = check_field(&field) {
+    ///         diag.emit_and_abort_if(field.is_required());
+    ///     }
+    /// }
+    /// ```
+    pub fn emit_and_abort_if(self, cond: bool) {
+        self.emit();
+        if cond {
+            abort_now()
+        }
+    }
+
+    /// Same as [`emit`](#method.emit), but emits a copy and leaves `self` intact,
+    /// e.g. to also keep it for a summary at the end.
+    pub fn emit_ref(&self) {
+        self.clone().emit()
+    }
+
     /// Display the diagnostic while not aborting macro execution.
     ///
     /// # Warnings
     ///
     /// Warnings are ignored on stable/beta
-    pub fn emit(self) {
+    pub fn emit(mut self) {
         check_correctness();
+
+        #[cfg(feature = "debug-backtrace")]
+        {
+            if let Some(backtrace) = self.backtrace.take() {
+                self = self.note(backtrace);
+            }
+        }
+
+        if warn_as_error() {
+            self.promote_warnings();
+        }
+
+        if is_duplicate(&self) {
+            return;
+        }
+
+        call_emit_hook(&self);
+
+        #[cfg(feature = "tracing")]
+        self.trace();
+
+        #[cfg(feature = "testing")]
+        {
+            if crate::testing::is_capturing() {
+                crate::testing::capture(self);
+                return;
+            }
+        }
+
         crate::imp::emit_diagnostic(self);
     }
+
+    fn promote_warnings(&mut self) {
+        if self.level == Level::Warning {
+            self.level = Level::Error;
+        }
+        for child in &mut self.children {
+            child.promote_warnings();
+        }
+    }
+
+    /// Discard the diagnostic without displaying it.
+    ///
+    /// This is exactly what dropping the diagnostic does, but it states the intent
+    /// explicitly: the diagnostic was built on purpose and then deemed unnecessary.
+    pub fn cancel(self) {
+        drop(self)
+    }
 }
 
 /// **NOT PUBLIC API! NOTHING TO SEE HERE!!!**
@@ -198,39 +816,70 @@ impl Diagnostic {
 impl Diagnostic {
     pub fn span_suggestion(self, span: Span, suggestion: &str, msg: String) -> Self {
         match suggestion {
+            "code" => self.code(msg),
             "help" | "hint" => self.span_help(span, msg),
-            _ => self.span_note(span, msg),
+            "note" => self.span_note(span, msg),
+            label => self.custom_suggestion(label, msg, Some(SpanRange::single_span(span))),
         }
     }
 
     pub fn suggestion(self, suggestion: &str, msg: String) -> Self {
         match suggestion {
+            "code" => self.code(msg),
             "help" | "hint" => self.help(msg),
-            _ => self.note(msg),
+            "note" => self.note(msg),
+            label => self.custom_suggestion(label, msg, None),
+        }
+    }
+
+    fn custom_suggestion(mut self, label: &str, msg: String, span: Option<SpanRange>) -> Self {
+        self.suggestions
+            .push((SuggestionKind::Custom(label.to_string()), msg, span));
+        self
+    }
+
+    pub fn lazy_suggestion<F, T>(self, suggestion: &str, f: F) -> Self
+    where
+        F: FnOnce() -> Option<T>,
+        T: ToString,
+    {
+        // called even for warnings: they may end up reported as errors,
+        // via `warn_as_error` or `Diagnostic::set_level`
+        match f() {
+            Some(msg) => self.suggestion(suggestion, msg.to_string()),
+            None => self,
+        }
+    }
+
+    pub fn lazy_span_suggestion<F, T>(self, span: Span, suggestion: &str, f: F) -> Self
+    where
+        F: FnOnce() -> Option<T>,
+        T: ToString,
+    {
+        match f() {
+            Some(msg) => self.span_suggestion(span, suggestion, msg.to_string()),
+            None => self,
         }
     }
 }
 
+/// Follows the `single_line` setting of the current entry point, if any.
 impl ToTokens for Diagnostic {
     fn to_tokens(&self, ts: &mut TokenStream) {
-        use std::borrow::Cow;
-
-        fn ensure_lf(buf: &mut String, s: &str) {
-            if s.ends_with('\n') {
-                buf.push_str(s);
-            } else {
-                buf.push_str(s);
-                buf.push('\n');
-            }
-        }
+        self.to_tokens_impl(ts, single_line())
+    }
+}
 
+impl Diagnostic {
+    pub(crate) fn to_tokens_impl(&self, ts: &mut TokenStream, single_line: bool) {
         fn diag_to_tokens(
             span_range: SpanRange,
             level: &Level,
             msg: &str,
-            suggestions: &[(SuggestionKind, String, Option<SpanRange>)],
+            suggestions: &[&(SuggestionKind, String, Option<SpanRange>)],
+            single_line: bool,
         ) -> TokenStream {
-            if *level == Level::Warning {
+            if !level.is_error() {
                 return TokenStream::new();
             }
 
@@ -239,111 +888,674 @@ impl ToTokens for Diagnostic {
             } else {
                 let mut message = String::new();
                 ensure_lf(&mut message, msg);
-                message.push('\n');
 
-                for (kind, note, _span) in suggestions {
-                    message.push_str("  = ");
-                    message.push_str(kind.name());
-                    message.push_str(": ");
-                    ensure_lf(&mut message, note);
+                for (kind, note, span) in suggestions {
+                    push_suggestion(&mut message, kind, &with_location(note, *span));
                 }
-                message.push('\n');
+                message.pop(); // no trailing line feed
 
                 Cow::Owned(message)
             };
 
+            let message = if single_line {
+                Cow::Owned(to_single_line(&message))
+            } else {
+                message
+            };
+
             let mut msg = proc_macro2::Literal::string(&message);
             msg.set_span(span_range.last);
             let group = quote_spanned!(span_range.last=> { #msg } );
             quote_spanned!(span_range.first=> compile_error!#group)
         }
 
+        // children are a part of the diagnostic, a warning (or a note) must not
+        // turn into errors just because it has error children
+        if !self.level.is_error() {
+            return;
+        }
+
+        let (spanned, inline): (Vec<_>, Vec<_>) = self
+            .suggestions
+            .iter()
+            .partition(|(_kind, _note, span)| self.spanned_notes_as_errors && span.is_some());
+
         ts.extend(diag_to_tokens(
             self.span_range,
             &self.level,
-            &self.msg,
-            &self.suggestions,
+            &main_message(&self.level, &self.code, &self.msg),
+            &inline,
+            single_line,
         ));
-        ts.extend(
-            self.children
-                .iter()
-                .map(|(span_range, msg)| diag_to_tokens(*span_range, &Level::Error, &msg, &[])),
-        );
+        for (kind, note, span_range) in spanned {
+            let msg = format!("{}: {}", kind.name(), kind.message(note));
+            ts.extend(diag_to_tokens(
+                span_range.unwrap(),
+                &Level::Error,
+                &msg,
+                &[],
+                single_line,
+            ));
+        }
+        for child in &self.children {
+            child.to_tokens_impl(ts, single_line);
+        }
     }
 }
 
-#[derive(Debug)]
+/// `a\n  = note: b` becomes `a; = note: b`.
+fn to_single_line(message: &str) -> String {
+    message
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// `compile_error!` has a single span, so the span of an inline note is lost.
+/// Point to it by its source text instead, this is as good as it gets on stable.
+fn with_location(note: &str, span_range: Option<SpanRange>) -> Cow<'_, str> {
+    let text = span_range.and_then(|range| range.collapse().source_text());
+    match text {
+        Some(text) => {
+            let mut lines = text.lines();
+            let first = lines.next().unwrap_or("");
+            let ellipsis = if lines.next().is_some() { "..." } else { "" };
+            Cow::Owned(format!("{} (at `{}{}`)", note, first, ellipsis))
+        }
+        None => Cow::Borrowed(note),
+    }
+}
+
+/// The main message as it's reported to the compiler: `label: [CODE] msg`.
+pub(crate) fn main_message<'a>(level: &Level, code: &Option<String>, msg: &'a str) -> Cow<'a, str> {
+    let msg = match code {
+        Some(code) => Cow::Owned(format!("[{}] {}", code, msg)),
+        None => Cow::Borrowed(msg),
+    };
+
+    match level {
+        Level::Custom(label) => Cow::Owned(format!("{}: {}", label, msg)),
+        _ => msg,
+    }
+}
+
+fn ensure_lf(buf: &mut String, s: &str) {
+    if s.ends_with('\n') {
+        buf.push_str(s);
+    } else {
+        buf.push_str(s);
+        buf.push('\n');
+    }
+}
+
+/// Render a suggestion as `  = name: msg\n`, the prefix is configurable via `set_note_prefix`.
+fn push_suggestion(buf: &mut String, kind: &SuggestionKind, msg: &str) {
+    let prefix = format!("{}{}: ", note_prefix(), kind.name());
+    buf.push_str(&prefix);
+
+    let msg = kind.message(msg);
+    if kind.is_indented() {
+        let indent = format!("\n{:width$}", "", width = prefix.len());
+        // a trailing line feed is not a continuation line
+        let body = msg.trim_end_matches('\n');
+        buf.push_str(&body.replace('\n', &indent));
+        buf.push('\n');
+    } else {
+        ensure_lf(buf, &msg);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub(crate) enum SuggestionKind {
     Help,
     Note,
+    IndentedHelp,
+    IndentedNote,
+    Replacement(String),
+    /// A note with a user-supplied label, like `yay` in `yay = "msg"`.
+    Custom(String),
 }
 
 impl SuggestionKind {
-    fn name(&self) -> &'static str {
+    fn name(&self) -> &str {
+        match self {
+            SuggestionKind::Note | SuggestionKind::IndentedNote => "note",
+            SuggestionKind::Help
+            | SuggestionKind::IndentedHelp
+            | SuggestionKind::Replacement(_) => "help",
+            SuggestionKind::Custom(label) => label,
+        }
+    }
+
+    /// rustc aligns continuation lines by itself on nightly, so
+    /// this only matters for stable and [`Diagnostic::render`].
+    fn is_indented(&self) -> bool {
+        // no `matches!` on the MSRV
+        *self == SuggestionKind::IndentedHelp || *self == SuggestionKind::IndentedNote
+    }
+
+    pub(crate) fn message<'a>(&self, msg: &'a str) -> Cow<'a, str> {
         match self {
-            SuggestionKind::Note => "note",
-            SuggestionKind::Help => "help",
+            SuggestionKind::Replacement(replacement) => {
+                Cow::Owned(format!("{}: `{}`", msg, replacement))
+            }
+            _ => Cow::Borrowed(msg),
+        }
+    }
+}
+
+/// What makes two diagnostics identical as far as the `dedup` setting
+/// and [`Diagnostic::dedup_children`] are concerned.
+///
+/// [`Diagnostic::dedup_children`]: struct.Diagnostic.html#method.dedup_children
+#[derive(PartialEq, Eq, Hash)]
+pub(crate) struct DiagnosticKey {
+    level: Level,
+    code: Option<String>,
+    msg: String,
+    span: SpanKey,
+    notes: Vec<(SuggestionKind, String, Option<SpanKey>)>,
+    children: Vec<DiagnosticKey>,
+}
+
+impl DiagnosticKey {
+    pub(crate) fn new(diag: &Diagnostic) -> Self {
+        DiagnosticKey {
+            level: diag.level.clone(),
+            code: diag.code.clone(),
+            msg: diag.msg.clone(),
+            span: SpanKey::new(diag.span_range),
+            notes: diag
+                .suggestions
+                .iter()
+                .map(|(kind, msg, span)| (kind.clone(), msg.clone(), span.map(SpanKey::new)))
+                .collect(),
+            children: diag.children.iter().map(DiagnosticKey::new).collect(),
+        }
+    }
+}
+
+/// The location of a span range: lines and columns when `proc-macro2` knows them,
+/// otherwise the byte range the compiler puts into the `Debug` output of its spans.
+/// Without `span-locations`, fallback spans carry no location at all and are
+/// indistinguishable from each other.
+#[derive(PartialEq, Eq, Hash)]
+enum SpanKey {
+    #[cfg_attr(
+        not(any(feature = "sort-diagnostics", feature = "json", feature = "miette")),
+        allow(dead_code)
+    )]
+    Position((usize, usize), (usize, usize)),
+    Opaque(String),
+}
+
+impl SpanKey {
+    fn new(span_range: SpanRange) -> Self {
+        #[cfg(any(feature = "sort-diagnostics", feature = "json", feature = "miette"))]
+        {
+            let start = span_range.first.start();
+            let end = span_range.last.end();
+            // line numbers are 1-based, zero means "unknown"
+            if start.line != 0 {
+                return SpanKey::Position((start.line, start.column), (end.line, end.column));
+            }
         }
+
+        SpanKey::Opaque(format!("{:?} {:?}", span_range.first, span_range.last))
+    }
+}
+
+/// An error pointing to the call site.
+///
+/// Warnings still need to be built explicitly, see [`Diagnostic::new`].
+///
+/// [`Diagnostic::new`]: struct.Diagnostic.html#method.new
+impl From<&str> for Diagnostic {
+    fn from(msg: &str) -> Self {
+        Diagnostic::new(Level::Error, msg.to_string())
+    }
+}
+
+/// An error pointing to the span.
+///
+/// Warnings still need to be built explicitly, see [`Diagnostic::spanned`].
+///
+/// [`Diagnostic::spanned`]: struct.Diagnostic.html#method.spanned
+impl From<(Span, &str)> for Diagnostic {
+    fn from((span, msg): (Span, &str)) -> Self {
+        Diagnostic::spanned(span, Level::Error, msg.to_string())
+    }
+}
+
+#[cfg(feature = "syn-error")]
+impl Diagnostic {
+    /// Build a diagnostic out of `syn::Error`.
+    ///
+    /// The first error becomes the main message, the rest of them (if `err` was
+    /// built via `syn::Error::combine`) are attached as children. Every message
+    /// keeps its own span range.
+    pub fn from_syn(err: &syn::Error) -> Diagnostic {
+        fn span_range(err: &syn::Error) -> SpanRange {
+            // `compile_error!` invocation generated by `syn` starts at
+            // the error's first span and ends at the last one.
+            SpanRange::from_tokens(&err.to_compile_error())
+        }
+
+        let mut errors = err.into_iter();
+        let main = errors.next().unwrap_or_else(|| err.clone());
+        let mut res = Diagnostic::spanned_range(span_range(&main), Level::Error, main.to_string());
+
+        for child in errors {
+            res = res.span_range_error(span_range(&child), child.to_string());
+        }
+
+        res
+    }
+
+    /// Same as [`from_syn`](#method.from_syn), but the main message underlines
+    /// `range` instead of the range inferred from `err`.
+    ///
+    /// Handy when the error was created from a single span and you know the true
+    /// extent of the offending node:
+    ///
+    /// ```ignore
+    /// let diag = Diagnostic::from_syn_spanned(&err, SpanRange::from_tokens(&field));
+    /// ```
+    pub fn from_syn_spanned(err: &syn::Error, range: impl Into<SpanRange>) -> Diagnostic {
+        Diagnostic::from_syn(err).with_spans(range.into())
+    }
+
+    /// Fold a collection of independent `syn::Error`s into one diagnostic:
+    /// the first one becomes the main message, the rest are attached as children.
+    /// `None` if there are no errors at all.
+    ///
+    /// Handy to report all the malformed elements of a list at once.
+    /// Children repeating an earlier one are dropped,
+    /// see [`dedup_children`](#method.dedup_children).
+    pub fn from_syn_errors<I>(errs: I) -> Option<Diagnostic>
+    where
+        I: IntoIterator<Item = syn::Error>,
+    {
+        let mut errs = errs.into_iter();
+        let mut res = errs.next()?;
+        for err in errs {
+            res.combine(err);
+        }
+
+        let mut res = Diagnostic::from_syn(&res);
+        res.dedup_children();
+        Some(res)
     }
 }
 
 #[cfg(feature = "syn-error")]
 impl From<syn::Error> for Diagnostic {
     fn from(err: syn::Error) -> Self {
-        use proc_macro2::{Delimiter, TokenTree};
+        Diagnostic::from_syn(&err)
+    }
+}
 
-        fn gut_error(ts: &mut impl Iterator<Item = TokenTree>) -> Option<(SpanRange, String)> {
-            let first = match ts.next() {
-                // compile_error
-                None => return None,
-                Some(tt) => tt.span(),
-            };
-            ts.next().unwrap(); // !
-
-            let lit = match ts.next().unwrap() {
-                TokenTree::Group(group) => {
-                    // Currently `syn` builds `compile_error!` invocations
-                    // exclusively in `ident{"..."}` (braced) form which is not
-                    // followed by `;` (semicolon).
-                    //
-                    // But if it changes to `ident("...");` (parenthesized)
-                    // or `ident["..."];` (bracketed) form,
-                    // we will need to skip the `;` as well.
-                    // Highly unlikely, but better safe than sorry.
-
-                    if group.delimiter() == Delimiter::Parenthesis
-                        || group.delimiter() == Delimiter::Bracket
-                    {
-                        ts.next().unwrap(); // ;
-                    }
+#[cfg(feature = "syn-error")]
+impl From<Diagnostic> for syn::Error {
+    fn from(diag: Diagnostic) -> Self {
+        fn to_syn_error(span_range: SpanRange, msg: String) -> syn::Error {
+            // `new_spanned` takes the range from the first and the last tokens,
+            // this is the only way to preserve the whole range on stable.
+            let mut tokens = quote_spanned!(span_range.first=> _);
+            tokens.extend(quote_spanned!(span_range.last=> _));
+            syn::Error::new_spanned(tokens, msg)
+        }
+
+        let Diagnostic {
+            level,
+            span_range,
+            msg,
+            suggestions,
+            children,
+            code,
+            ..
+        } = diag;
+
+        let mut msg = main_message(&level, &code, &msg).into_owned();
+
+        // `syn::Error` has no notion of notes, fold them into the message
+        // the same way the stable `compile_error!` output does
+        if !suggestions.is_empty() {
+            let main = msg;
+            msg = String::new();
+            ensure_lf(&mut msg, &main);
+            for (kind, note, span) in &suggestions {
+                push_suggestion(&mut msg, kind, &with_location(note, *span));
+            }
+            msg.pop(); // no trailing line feed
+        }
+
+        let mut res = to_syn_error(span_range, msg);
+        // `syn::Error` is always an error, warnings are dropped
+        for child in children {
+            if child.level.is_error() {
+                res.combine(syn::Error::from(child));
+            }
+        }
+
+        res
+    }
+}
+
+#[cfg(feature = "testing")]
+impl Diagnostic {
+    /// Compare two diagnostics by their text: level, message, code, notes and children.
+    /// Spans are ignored.
+    ///
+    /// Useful for assertions on diagnostics captured via
+    /// [`with_captured_diagnostics`](testing/fn.with_captured_diagnostics.html).
+    pub fn eq_ignoring_spans(&self, other: &Diagnostic) -> bool {
+        fn notes(diag: &Diagnostic) -> impl Iterator<Item = (&SuggestionKind, &String)> {
+            diag.suggestions
+                .iter()
+                .map(|(kind, msg, _span)| (kind, msg))
+        }
+
+        self.level == other.level
+            && self.msg == other.msg
+            && self.code == other.code
+            && notes(self).eq(notes(other))
+            && self.children.len() == other.children.len()
+            && self
+                .children
+                .iter()
+                .zip(&other.children)
+                .all(|(this, other)| this.eq_ignoring_spans(other))
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl Diagnostic {
+    fn trace(&self) {
+        let (first, last) = (self.span_range.first, self.span_range.last);
+        let msg = main_message(&self.level, &self.code, &self.msg);
+        match self.level {
+            Level::Warning => tracing::warn!(?first, ?last, "{}", msg),
+            Level::Note => tracing::info!(?first, ?last, "{}", msg),
+            _ => tracing::error!(?first, ?last, "{}", msg),
+        }
+    }
+}
+
+/// `PME_BACKTRACE=1` makes every diagnostic remember where it was created.
+#[cfg(feature = "debug-backtrace")]
+fn capture_backtrace() -> Option<String> {
+    // the frames past these are the compiler's internals, hardly of any interest
+    const MAX_LINES: usize = 40;
+
+    match std::env::var_os("PME_BACKTRACE") {
+        Some(ref var) if var == "1" => {}
+        _ => return None,
+    }
+
+    let backtrace = std::backtrace::Backtrace::force_capture().to_string();
+    let mut lines = backtrace.lines();
+    let mut res = String::from("backtrace:\n");
+    for line in lines.by_ref().take(MAX_LINES) {
+        res.push_str(line);
+        res.push('\n');
+    }
+    if lines.next().is_some() {
+        res.push_str("...\n");
+    }
+    Some(res)
+}
+
+#[cfg(feature = "json")]
+impl Diagnostic {
+    /// Serialize the diagnostic into JSON, modeled after `rustc --error-format=json`:
+    ///
+    /// ```text
+    /// {
+    ///     "message": "main message",
+    ///     "code": {"code": "E0042", "explanation": null} | null,
+    ///     "level": "error" | "warning" | "note" | "help",
+    ///     "label": "lint" | null,
+    ///     "spans": [{
+    ///         "line_start": 1, "column_start": 1,
+    ///         "line_end": 1, "column_end": 4,
+    ///         "is_primary": true,
+    ///         "suggested_replacement": "replacement" | null
+    ///     }],
+    ///     "children": [ /* notes and children diagnostics, same structure */ ]
+    /// }
+    /// ```
+    ///
+    /// `level` is always one of `rustc`'s levels: [custom levels] are errors and custom
+    /// note labels are notes, `label` holds the custom label.
+    ///
+    /// Lines and columns are 1-based. Spans are located on the best-effort basis:
+    /// if the location is unknown (e.g. on stable inside a proc-macro), `spans` is empty.
+    ///
+    /// [custom levels]: enum.Level.html#variant.Custom
+    pub fn to_json(&self) -> String {
+        fn json_str(buf: &mut String, s: &str) {
+            buf.push('"');
+            for c in s.chars() {
+                match c {
+                    '"' => buf.push_str("\\\""),
+                    '\\' => buf.push_str("\\\\"),
+                    '\n' => buf.push_str("\\n"),
+                    '\r' => buf.push_str("\\r"),
+                    '\t' => buf.push_str("\\t"),
+                    c if c < ' ' => buf.push_str(&format!("\\u{:04x}", c as u32)),
+                    c => buf.push(c),
+                }
+            }
+            buf.push('"');
+        }
 
-                    match group.stream().into_iter().next().unwrap() {
-                        TokenTree::Literal(lit) => lit,
-                        _ => unreachable!(),
+        fn json_span(buf: &mut String, span_range: Option<SpanRange>, replacement: Option<&str>) {
+            buf.push_str("\"spans\":[");
+            if let Some(span_range) = span_range {
+                let start = span_range.first.start();
+                let end = span_range.last.end();
+                // line numbers are 1-based, zero means "unknown"
+                if start.line != 0 {
+                    buf.push_str(&format!(
+                        "{{\"line_start\":{},\"column_start\":{},\
+                         \"line_end\":{},\"column_end\":{},\"is_primary\":true,\
+                         \"suggested_replacement\":",
+                        start.line,
+                        start.column + 1,
+                        end.line,
+                        end.column + 1,
+                    ));
+                    match replacement {
+                        Some(replacement) => json_str(buf, replacement),
+                        None => buf.push_str("null"),
                     }
+                    buf.push('}');
                 }
-                _ => unreachable!(),
+            }
+            buf.push(']');
+        }
+
+        fn json_diag(
+            buf: &mut String,
+            level: &str,
+            label: Option<&str>,
+            msg: &str,
+            code: &Option<String>,
+            span_range: Option<SpanRange>,
+            replacement: Option<&str>,
+        ) {
+            buf.push_str("{\"message\":");
+            json_str(buf, msg);
+            buf.push_str(",\"code\":");
+            match code {
+                Some(code) => {
+                    buf.push_str("{\"code\":");
+                    json_str(buf, code);
+                    buf.push_str(",\"explanation\":null}");
+                }
+                None => buf.push_str("null"),
+            }
+            buf.push_str(",\"level\":");
+            json_str(buf, level);
+            buf.push_str(",\"label\":");
+            match label {
+                Some(label) => json_str(buf, label),
+                None => buf.push_str("null"),
+            }
+            buf.push(',');
+            json_span(buf, span_range, replacement);
+        }
+
+        let (level, label) = match &self.level {
+            Level::Error | Level::NonExhaustive => ("error", None),
+            Level::Warning => ("warning", None),
+            Level::Note => ("note", None),
+            Level::Custom(label) => ("error", Some(label.as_str())),
+        };
+        let mut res = String::new();
+        json_diag(
+            &mut res,
+            level,
+            label,
+            &self.msg,
+            &self.code,
+            Some(self.span_range),
+            None,
+        );
+
+        res.push_str(",\"children\":[");
+        let suggestions = self.suggestions.iter().map(|(kind, msg, span_range)| {
+            let replacement = match kind {
+                SuggestionKind::Replacement(replacement) => Some(replacement.as_str()),
+                _ => None,
+            };
+            let (level, label) = match kind {
+                SuggestionKind::Custom(label) => ("note", Some(label.as_str())),
+                _ => (kind.name(), None),
             };
+            (level, label, msg, *span_range, replacement)
+        });
+        for (i, (level, label, msg, span_range, replacement)) in suggestions.enumerate() {
+            if i != 0 {
+                res.push(',');
+            }
+            json_diag(&mut res, level, label, msg, &None, span_range, replacement);
+            res.push_str(",\"children\":[]}");
+        }
+        for (i, child) in self.children.iter().enumerate() {
+            if i != 0 || !self.suggestions.is_empty() {
+                res.push(',');
+            }
+            res.push_str(&child.to_json());
+        }
+        res.push_str("]}");
 
-            let last = lit.span();
-            let mut msg = lit.to_string();
+        res
+    }
+}
 
-            // "abc" => abc
-            msg.pop();
-            msg.remove(0);
+#[cfg(feature = "miette")]
+impl From<Diagnostic> for miette_crate::MietteDiagnostic {
+    fn from(diag: Diagnostic) -> Self {
+        use miette_crate::{LabeledSpan, MietteDiagnostic, Severity};
 
-            Some((SpanRange { first, last }, msg))
+        // Spans are resolved to byte offsets only outside of rustc,
+        // otherwise they are all `0..0`.
+        fn byte_range(span_range: SpanRange) -> Option<std::ops::Range<usize>> {
+            let start = span_range.first.byte_range().start;
+            let end = span_range.last.byte_range().end;
+            if start < end {
+                Some(start..end)
+            } else {
+                None
+            }
         }
 
-        let mut ts = err.to_compile_error().into_iter();
+        let Diagnostic {
+            level,
+            span_range,
+            msg,
+            suggestions,
+            children,
+            code,
+            ..
+        } = diag;
 
-        let (span_range, msg) = gut_error(&mut ts).unwrap();
-        let mut res = Diagnostic::spanned_range(span_range, Level::Error, msg);
+        let severity = match level {
+            Level::Warning => Severity::Warning,
+            Level::Note => Severity::Advice,
+            _ => Severity::Error,
+        };
+        let msg = match level {
+            Level::Custom(label) => format!("{}: {}", label, msg),
+            _ => msg,
+        };
+        let mut res = MietteDiagnostic::new(msg).with_severity(severity);
 
-        while let Some((span_range, msg)) = gut_error(&mut ts) {
-            res = res.span_range_error(span_range, msg);
+        if let Some(code) = code {
+            res = res.with_code(code);
+        }
+
+        if let Some(range) = byte_range(span_range) {
+            res = res.with_label(LabeledSpan::new_primary_with_span(None, range));
+        }
+
+        fn push_notes(
+            help: &mut Vec<String>,
+            suggestions: &[(SuggestionKind, String, Option<SpanRange>)],
+        ) {
+            help.extend(
+                suggestions
+                    .iter()
+                    .map(|(kind, note, _span)| format!("{}: {}", kind.name(), kind.message(note))),
+            );
+        }
+
+        // children (at any depth) become labels, the ones that can't
+        // be located go to help, their notes go to help as well
+        fn push_children(
+            labels: &mut Vec<LabeledSpan>,
+            help: &mut Vec<String>,
+            children: Vec<Diagnostic>,
+        ) {
+            for child in children {
+                // `Level::Error` since the label is already there
+                let msg = format!(
+                    "{}: {}",
+                    child.level.name(),
+                    main_message(&Level::Error, &child.code, &child.msg)
+                );
+                match byte_range(child.span_range) {
+                    Some(range) => labels.push(LabeledSpan::new_with_span(Some(msg), range)),
+                    None => help.push(msg),
+                }
+                push_notes(help, &child.suggestions);
+                push_children(labels, help, child.children);
+            }
+        }
+
+        let mut help = Vec::new();
+        let mut labels = Vec::new();
+        push_notes(&mut help, &suggestions);
+        push_children(&mut labels, &mut help, children);
+        if !labels.is_empty() {
+            res = res.and_labels(labels);
+        }
+
+        if !help.is_empty() {
+            res = res.with_help(help.join("\n"));
         }
 
         res
     }
 }
+
+#[cfg(feature = "miette")]
+impl From<Diagnostic> for miette_crate::Report {
+    fn from(diag: Diagnostic) -> Self {
+        miette_crate::Report::new(miette_crate::MietteDiagnostic::from(diag))
+    }
+}
@@ -112,6 +112,20 @@
 //!      Foo::do_thing(); // no more errors!
 //!  }
 //! ```
+//!
+//! # Several contributors
+//!
+//! - [`set_dummy`] replaces the whole dummy, returning the previous one.
+//! - [`append_dummy`] appends to the dummy set earlier, meant to extend it.
+//! - [`add_dummy`] appends to the dummy, or initializes it if there's none yet.
+//!
+//! So independent helpers that each want to contribute a piece of the dummy
+//! should use [`add_dummy`]: neither of them needs to know whether the other
+//! one has run, and neither stomps on the other's tokens.
+//!
+//! [`set_dummy`]: fn.set_dummy.html
+//! [`append_dummy`]: fn.append_dummy.html
+//! [`add_dummy`]: fn.add_dummy.html
 
 use proc_macro2::TokenStream;
 use std::cell::RefCell;
@@ -133,7 +147,22 @@ pub fn set_dummy(dummy: TokenStream) -> Option<TokenStream> {
 
 /// Same as [`set_dummy`] but, instead of resetting, appends tokens to the
 /// existing dummy (if any). Behaves as `set_dummy` if no dummy is present.
+///
+/// The tokens are concatenated in call order: `set_dummy(a)` followed by
+/// `append_dummy(b)` and `append_dummy(c)` results in `a b c`. The whole dummy is
+/// placed right after all the `compile_error!` invocations.
 pub fn append_dummy(dummy: TokenStream) {
+    add_dummy(dummy)
+}
+
+/// Contribute a piece of the dummy: append the tokens to the existing dummy,
+/// or initialize the dummy with them if there's none yet.
+///
+/// Unlike [`set_dummy`], it never discards what others have contributed,
+/// see [several contributors](index.html#several-contributors).
+///
+/// [`set_dummy`]: fn.set_dummy.html
+pub fn add_dummy(dummy: TokenStream) {
     check_correctness();
     DUMMY_IMPL.with(|old_dummy| {
         let mut cell = old_dummy.borrow_mut();
@@ -145,6 +174,19 @@ pub fn append_dummy(dummy: TokenStream) {
     });
 }
 
+/// Removes the dummy token stream (if any) so nothing will be appended to
+/// `compile_error!(msg);...` invocations, returns the removed dummy.
+///
+/// Useful when the dummy set earlier is no longer appropriate.
+pub fn clear_dummy() -> Option<TokenStream> {
+    check_correctness();
+    cleanup()
+}
+
 pub(crate) fn cleanup() -> Option<TokenStream> {
-    DUMMY_IMPL.with(|old_dummy| old_dummy.replace(None))
+    replace(None)
+}
+
+pub(crate) fn replace(dummy: Option<TokenStream>) -> Option<TokenStream> {
+    DUMMY_IMPL.with(|old_dummy| old_dummy.replace(dummy))
 }
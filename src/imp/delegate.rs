@@ -1,23 +1,62 @@
 //! This implementation uses [`proc_macro::Diagnostic`], nightly only.
 
 use std::cell::Cell;
+use std::panic::catch_unwind;
 
-use proc_macro::{Diagnostic as PDiag, Level as PLevel};
+use proc_macro::{Diagnostic as PDiag, Level as PLevel, Span};
 
 use crate::{
-    abort_now, check_correctness,
-    diagnostic::{Diagnostic, Level, SuggestionKind},
+    check_correctness,
+    diagnostic::{main_message, Diagnostic, Level, SuggestionKind},
+    max_errors, summary, suppressed_message, SpanRange,
 };
 
-pub fn abort_if_dirty() {
+pub fn error_count() -> usize {
     check_correctness();
-    if IS_DIRTY.with(|c| c.get()) {
-        abort_now()
+    ERR_COUNT.with(|c| c.get())
+}
+
+pub fn warning_count() -> usize {
+    check_correctness();
+    WARN_COUNT.with(|c| c.get())
+}
+
+pub(crate) fn drain_errors() -> Vec<Diagnostic> {
+    vec![]
+}
+
+/// The error and warning counts of an enclosing entry point, set aside
+/// while a nested one runs.
+pub(crate) struct SavedState {
+    errors: usize,
+    warnings: usize,
+}
+
+pub(crate) fn save_state() -> SavedState {
+    SavedState {
+        errors: ERR_COUNT.with(|c| c.replace(0)),
+        warnings: WARN_COUNT.with(|c| c.replace(0)),
     }
 }
 
+pub(crate) fn restore_state(state: SavedState) {
+    ERR_COUNT.with(|c| c.set(state.errors));
+    WARN_COUNT.with(|c| c.set(state.warnings));
+}
+
 pub(crate) fn cleanup() -> Vec<Diagnostic> {
-    IS_DIRTY.with(|c| c.set(false));
+    let count = ERR_COUNT.with(|c| c.replace(0));
+    // no compiler to report to outside of a proc-macro, see `emit_diagnostic`
+    let available = proc_macro::is_available();
+    if let Some(max) = max_errors() {
+        if count > max && available {
+            PDiag::new(PLevel::Error, suppressed_message(count - max)).emit();
+        }
+    }
+    if summary() && count > 1 && available {
+        PDiag::new(PLevel::Error, format!("{} errors found", count)).emit();
+    }
+    WARN_COUNT.with(|c| c.set(0));
     vec![]
 }
 
@@ -28,42 +67,100 @@ pub(crate) fn emit_diagnostic(diag: Diagnostic) {
         msg,
         suggestions,
         children,
+        code,
+        unspanned,
+        ..
     } = diag;
 
-    let span = span_range.collapse().unwrap();
-
+    let msg = main_message(&level, &code, &msg).into_owned();
     let level = match level {
-        Level::Warning => PLevel::Warning,
-        Level::Error => {
-            IS_DIRTY.with(|c| c.set(true));
+        Level::Warning => {
+            WARN_COUNT.with(|c| c.set(c.get() + 1));
+            PLevel::Warning
+        }
+        Level::Note => PLevel::Note,
+        Level::Error | Level::Custom(_) | Level::NonExhaustive => {
+            let count = ERR_COUNT.with(|c| {
+                c.set(c.get() + 1);
+                c.get()
+            });
+            // still counted, but not reported
+            if max_errors().map_or(false, |max| count > max) {
+                return;
+            }
             PLevel::Error
         }
-        _ => unreachable!(),
     };
 
-    let mut res = PDiag::spanned(span, level, msg);
+    // Outside of a proc-macro (e.g. in a regular `#[test]`) there's no compiler
+    // to report to, and the spans are `proc_macro2`'s own (fallback) ones.
+    // The diagnostic is counted, but not reported.
+    if !proc_macro::is_available() {
+        return;
+    }
 
+    let res = if unspanned {
+        PDiag::new(level, msg)
+    } else {
+        PDiag::spanned(to_pm_span(span_range), level, msg)
+    };
+    let res = attach_suggestions(res, suggestions);
+    attach_children(res, children).emit()
+}
+
+fn attach_suggestions(
+    mut res: PDiag,
+    suggestions: impl IntoIterator<Item = (SuggestionKind, String, Option<SpanRange>)>,
+) -> PDiag {
     for (kind, msg, span) in suggestions {
-        res = match (kind, span) {
-            (SuggestionKind::Note, Some(span_range)) => {
-                res.span_note(span_range.collapse().unwrap(), msg)
-            }
-            (SuggestionKind::Help, Some(span_range)) => {
-                res.span_help(span_range.collapse().unwrap(), msg)
-            }
-            (SuggestionKind::Note, None) => res.note(msg),
-            (SuggestionKind::Help, None) => res.help(msg),
+        let (is_note, msg) = match kind {
+            SuggestionKind::Note | SuggestionKind::IndentedNote => (true, msg),
+            // `proc_macro::Diagnostic` knows only "note" and "help",
+            // keep the label as a part of the message
+            SuggestionKind::Custom(label) => (true, format!("{}: {}", label, msg)),
+            kind => (false, kind.message(&msg).into_owned()),
+        };
+        res = match (is_note, span) {
+            (true, Some(span_range)) => res.span_note(to_pm_span(span_range), msg),
+            (false, Some(span_range)) => res.span_help(to_pm_span(span_range), msg),
+            (true, None) => res.note(msg),
+            (false, None) => res.help(msg),
         }
     }
+    res
+}
 
-    for (span_range, msg) in children {
-        let span = span_range.collapse().unwrap();
-        res = res.span_error(span, msg);
+// `proc_macro::Diagnostic` children can't have children of their own,
+// the whole tree is flattened: the notes and children of a child go
+// right after the child.
+fn attach_children(mut res: PDiag, children: Vec<Diagnostic>) -> PDiag {
+    for child in children {
+        let span = to_pm_span(child.span_range);
+        let msg = main_message(&child.level, &child.code, &child.msg).into_owned();
+        res = match child.level {
+            Level::Warning => res.span_warning(span, msg),
+            Level::Note => res.span_note(span, msg),
+            _ => res.span_error(span, msg),
+        };
+        res = attach_suggestions(res, child.suggestions);
+        res = attach_children(res, child.children);
     }
+    res
+}
 
-    res.emit()
+/// Turn the range into `proc_macro::Span`. Only called inside a proc-macro,
+/// where `proc_macro2` spans are normally backed by the compiler. Fallback spans
+/// (e.g. created while `proc_macro2::fallback::force()` was in effect) point
+/// to the call site instead, `Span::unwrap` panics on them.
+fn to_pm_span(span_range: SpanRange) -> Span {
+    let span = span_range.collapse();
+    // `proc_macro2` offers no way to tell a fallback span apart, unwinding is
+    // the only option. The compiler hides panics inside a running proc-macro
+    // so nothing is printed, and `Span::call_site()` is always available here.
+    catch_unwind(|| span.unwrap()).unwrap_or_else(|_| Span::call_site())
 }
 
 thread_local! {
-    static IS_DIRTY: Cell<bool> = Cell::new(false);
+    static ERR_COUNT: Cell<usize> = Cell::new(0);
+    static WARN_COUNT: Cell<usize> = Cell::new(0);
 }
@@ -1,30 +1,97 @@
 //! This implementation uses self-written stable facilities.
 
 use crate::{
-    abort_now, check_correctness,
+    check_correctness,
     diagnostic::{Diagnostic, Level},
+    max_errors, summary, suppressed_message,
 };
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 
-pub fn abort_if_dirty() {
+pub fn error_count() -> usize {
     check_correctness();
-    ERR_STORAGE.with(|storage| {
-        if !storage.borrow().is_empty() {
-            abort_now()
-        }
-    });
+    ERR_STORAGE.with(|storage| storage.borrow().len())
+}
+
+pub fn warning_count() -> usize {
+    check_correctness();
+    WARN_COUNT.with(|c| c.get())
+}
+
+pub(crate) fn drain_errors() -> Vec<Diagnostic> {
+    let mut storage = ERR_STORAGE.with(|storage| storage.replace(Vec::new()));
+    sort_diagnostics(&mut storage);
+    storage
+}
+
+/// The errors and warnings of an enclosing entry point, set aside
+/// while a nested one runs.
+pub(crate) struct SavedState {
+    errors: Vec<Diagnostic>,
+    warnings: usize,
+}
+
+pub(crate) fn save_state() -> SavedState {
+    SavedState {
+        errors: ERR_STORAGE.with(|storage| storage.replace(Vec::new())),
+        warnings: WARN_COUNT.with(|c| c.replace(0)),
+    }
+}
+
+pub(crate) fn restore_state(state: SavedState) {
+    let SavedState { errors, warnings } = state;
+    ERR_STORAGE.with(|storage| storage.replace(errors));
+    WARN_COUNT.with(|c| c.set(warnings));
 }
 
 pub(crate) fn cleanup() -> Vec<Diagnostic> {
-    ERR_STORAGE.with(|storage| storage.replace(Vec::new()))
+    WARN_COUNT.with(|c| c.set(0));
+    let mut storage = drain_errors();
+    let total = storage.len();
+
+    if let Some(max) = max_errors() {
+        if storage.len() > max {
+            let suppressed = storage.len() - max;
+            storage.truncate(max);
+            storage.push(Diagnostic::new(
+                Level::Error,
+                suppressed_message(suppressed),
+            ));
+        }
+    }
+
+    if summary() && total > 1 {
+        storage.push(Diagnostic::new(
+            Level::Error,
+            format!("{} errors found", total),
+        ));
+    }
+
+    storage
+}
+
+#[cfg(feature = "sort-diagnostics")]
+fn sort_diagnostics(diagnostics: &mut [Diagnostic]) {
+    // The sort is stable: if positions are unknown (all zeros),
+    // the diagnostics just keep the emission order.
+    diagnostics.sort_by_key(|diag| {
+        let start = diag.span_range.first.start();
+        (start.line, start.column)
+    });
 }
 
+#[cfg(not(feature = "sort-diagnostics"))]
+fn sort_diagnostics(_diagnostics: &mut [Diagnostic]) {}
+
 pub(crate) fn emit_diagnostic(diag: Diagnostic) {
-    if diag.level == Level::Error {
+    if diag.level.is_error() {
         ERR_STORAGE.with(|storage| storage.borrow_mut().push(diag));
+    } else if diag.is_warning() {
+        // rustc won't display it, but it can be counted at least
+        WARN_COUNT.with(|c| c.set(c.get() + 1));
     }
 }
 
 thread_local! {
     static ERR_STORAGE: RefCell<Vec<Diagnostic>> = RefCell::new(Vec::new());
+    static WARN_COUNT: Cell<usize> = Cell::new(0);
 }
@@ -16,7 +16,9 @@
 //! ## Cargo features
 //!
 //! This crate provides *enabled by default* `syn-error` feature that gates
-//! `impl From<syn::Error> for Diagnostic` conversion. If you don't use `syn` and want
+//! everything `syn`-related: `impl From<syn::Error> for Diagnostic`,
+//! `impl From<Diagnostic> for syn::Error` and `Diagnostic::from_syn`.
+//! The rest of the crate doesn't need `syn` at all. If you don't use `syn` and want
 //! to cut off some of compilation time, you can disable it via
 //!
 //! ```toml
@@ -27,6 +29,62 @@
 //! ***Please note that disabling this feature makes sense only if you don't depend on `syn`
 //! directly or indirectly, and you very likely do.**
 //!
+//! There's also the `testing` feature, *disabled by default*, that enables the
+//! [`testing`](testing/index.html) module - a facility to test your error reporting
+//! code in regular `#[test]`s, without compiling anything with `rustc`.
+//!
+//! The `sort-diagnostics` feature, *disabled by default*, makes the errors appear
+//! in the order of their position in the source code rather than in the order they
+//! were emitted. Caveats:
+//!
+//! - It has effect only on stable/beta; on nightly diagnostics are reported to
+//!   the compiler right away, in emission order.
+//! - It relies on `proc_macro2::Span::start()`, and spans carry no location
+//!   information on older compilers, so the emission order is kept there.
+//! - It enables `proc-macro2/span-locations` which may slow down other
+//!   `proc-macro2` users in your dependency tree a bit.
+//!
+//! The `miette` feature, *disabled by default*, provides conversions from [`Diagnostic`]
+//! to `miette::MietteDiagnostic` and `miette::Report`, for when your validation logic is
+//! also used outside of the compiler. Notes and help messages are joined into `help`,
+//! children become labels. Caveats:
+//!
+//! - Spans are known as byte offsets only outside of rustc. Inside a proc-macro
+//!   no labels are attached and the children's messages go to `help` as well.
+//! - It enables `proc-macro2/span-locations`, see the caveat above.
+//! - `miette` 7 requires Rust 1.70 or newer.
+//!
+//! The `json` feature, *disabled by default*, adds `Diagnostic::to_json` that
+//! serializes a diagnostic in the spirit of `rustc --error-format=json`, for tooling
+//! that runs macros outside of the compiler. It enables `proc-macro2/span-locations`
+//! as well.
+//!
+//! The `smallvec` feature, *disabled by default*, makes diagnostics store up to two
+//! notes inline, without allocating. It's purely an optimization,
+//! the API is the same.
+//!
+//! The `tracing` feature, *disabled by default*, makes every emitted diagnostic
+//! also fire a `tracing` event (`error` or `warn`, depending on the level) carrying
+//! the message and the spans, so you can observe the flow of diagnostics with
+//! `RUST_LOG` while running `cargo expand` or tests. The generated code is not affected.
+//! Requires Rust 1.65 or newer.
+//!
+//! The `color` feature, *disabled by default*, adds `Level::color` and
+//! `Diagnostic::render_colored` that color the levels with ANSI escape codes, for
+//! custom drivers rendering diagnostics outside of the compiler. The generated
+//! code is not affected.
+//!
+//! The `debug-backtrace` feature, *disabled by default*, helps to find out where
+//! a diagnostic came from: with `PME_BACKTRACE=1` set in the environment, every
+//! diagnostic captures a backtrace when created and gets it attached as a note
+//! (truncated) when emitted. Without the variable, nothing is captured. Requires
+//! Rust 1.65 or newer.
+//!
+//! The `force-fallback` feature, *disabled by default*, makes the crate use the stable
+//! implementation even on nightly. This is useful for testing your macros' stable
+//! behavior on nightly CI. It has no effect on stable and beta, the fallback is used
+//! there anyway.
+//!
 //! ## Real world examples
 //!
 //! * [`structopt-derive`](https://github.com/TeXitoi/structopt/tree/master/structopt-derive)
@@ -37,9 +95,16 @@
 //!
 //! - Warnings are emitted only on nightly, they are ignored on stable.
 //! - "help" suggestions can't have their own span info on stable,
-//!   (essentially inheriting the parent span).
+//!   (essentially inheriting the parent span). See `Diagnostic::spanned_notes_as_errors`
+//!   for a workaround.
 //! - If a panic occurs somewhere in your macro no errors will be displayed. This is not a
 //!   technical limitation but rather intentional design. `panic` is not for error reporting.
+//!   Use the `emit_on_panic` setting if you'd rather see them anyway.
+//! - All the state (emitted errors, the dummy, settings) is thread-local. Errors must be
+//!   emitted on the thread that runs the `#[proc_macro_error]` function, emitting on another
+//!   thread panics as "used outside of entry_point". If you generate code on worker threads,
+//!   send the [`Diagnostic`]s back and emit them there. This can't be lifted: spans
+//!   (and hence diagnostics) are not `Send` when the macro runs inside the compiler.
 //!
 //! ### `#[proc_macro_error]` attribute
 //!
@@ -47,6 +112,7 @@
 //! annotated with any of `#[proc_macro]`, `#[proc_macro_derive]`, `#[proc_macro_attribute]`).
 //!
 //! This attribute performs the setup and cleanup necessary to make things work.
+//! If you'd rather do it by hand, see [`catch`](fn.catch.html).
 //!
 //! In most cases you'll need the simple `#[proc_macro_error]` form without any
 //! additional settings. Feel free to [skip the "Syntax" section](#macros).
@@ -58,54 +124,111 @@
 //!
 //! - `proc_macro_hack`:
 //!
-//!     In order to correctly cooperate with `#[proc_macro_hack]`, `#[proc_macro_error]`
-//!     attribute must be placed *before* (above) it, like this:
-//!
-//!     ```no_run
-//!     # use proc_macro2::TokenStream;
-//!     # const IGNORE: &str = "
-//!     #[proc_macro_error]
-//!     #[proc_macro_hack]
-//!     #[proc_macro]
-//!     # ";
-//!     fn my_macro(input: TokenStream) -> TokenStream {
-//!         unimplemented!()
-//!     }
-//!     ```
+//!   In order to correctly cooperate with `#[proc_macro_hack]`, `#[proc_macro_error]`
+//!   attribute must be placed *before* (above) it, like this:
+//!
+//!   ```no_run
+//!   # use proc_macro2::TokenStream;
+//!   # const IGNORE: &str = "
+//!   #[proc_macro_error]
+//!   #[proc_macro_hack]
+//!   #[proc_macro]
+//!   # ";
+//!   fn my_macro(input: TokenStream) -> TokenStream {
+//!       unimplemented!()
+//!   }
+//!   ```
 //!
-//!     If, for some reason, you can't place it like that you can use
-//!     `#[proc_macro_error(proc_macro_hack)]` instead.
+//!   If, for some reason, you can't place it like that you can use
+//!   `#[proc_macro_error(proc_macro_hack)]` instead.
 //!
-//!     # Note
+//!   # Note
 //!
-//!     If `proc-macro-hack` was detected (by any means) `allow_not_macro`
-//!     and `assert_unwind_safe` will be applied automatically.
+//!   If `proc-macro-hack` was detected (by any means) `allow_not_macro`
+//!   and `assert_unwind_safe` will be applied automatically.
 //!
 //! - `allow_not_macro`:
 //!
-//!     By default, the attribute checks that it's applied to a proc-macro.
-//!     If none of `#[proc_macro]`, `#[proc_macro_derive]` nor `#[proc_macro_attribute]` are
-//!     present it will panic. It's the intention - this crate is supposed to be used only with
-//!     proc-macros.
+//!   By default, the attribute checks that it's applied to a proc-macro.
+//!   If none of `#[proc_macro]`, `#[proc_macro_derive]` nor `#[proc_macro_attribute]` are
+//!   present it will panic. It's the intention - this crate is supposed to be used only with
+//!   proc-macros.
 //!
-//!     This setting is made to bypass the check, useful in certain circumstances.
+//!   This setting is made to bypass the check, useful in certain circumstances.
 //!
-//!     Pay attention: the function this attribute is applied to must return
-//!     `proc_macro::TokenStream`.
+//!   Pay attention: the function this attribute is applied to must return
+//!   `proc_macro::TokenStream` or `proc_macro2::TokenStream`. The latter makes
+//!   the diagnostic machinery usable in `proc_macro2`-only code, e.g. codegen helpers.
+//!   The errors emitted inside are turned into `compile_error!` invocations
+//!   (or reported right away on nightly) when the helper returns.
 //!
-//!     This setting is implied if `proc-macro-hack` was detected.
+//!   This setting is implied if `proc-macro-hack` was detected.
 //!
 //! - `assert_unwind_safe`:
 //!
-//!     By default, your code must be [unwind safe]. If your code is not unwind safe,
-//!     but you believe it's correct, you can use this setting to bypass the check.
-//!     You would need this for code that uses `lazy_static` or `thread_local` with
-//!     `Cell/RefCell` inside (and the like).
+//!   By default, your code must be [unwind safe]. If your code is not unwind safe,
+//!   but you believe it's correct, you can use this setting to bypass the check.
+//!   You would need this for code that uses `lazy_static` or `thread_local` with
+//!   `Cell/RefCell` inside (and the like).
+//!
+//!   This setting is implied if `#[proc_macro_error]` is applied to a function
+//!   marked as `#[proc_macro]`, `#[proc_macro_derive]` or `#[proc_macro_attribute]`.
+//!
+//!   This setting is also implied if `proc-macro-hack` was detected.
+//!
+//! - `crate = path::to::proc_macro_error`:
+//!
+//!   By default, the attribute expects this crate to be available as `::proc_macro_error`.
+//!   If you renamed it in your `Cargo.toml` or use it via a re-export from
+//!   another crate, you need to specify the actual path to it.
+//!
+//! - `dedup`:
+//!
+//!   Sometimes the very same error may be emitted multiple times, e.g. if your
+//!   macro walks a tree with repeated constructs. With this setting on, a diagnostic
+//!   is silently dropped if an identical one (same level, span, message, notes
+//!   and children) has already been emitted.
+//!
+//! - `warn_as_error`:
+//!
+//!   Turn all the warnings (including children warnings) into errors, the compilation
+//!   fails if there were any. Useful for strict CI builds. Since warnings are ignored
+//!   on stable, this is also the only way to see them there. On nightly the warnings are
+//!   displayed as errors.
+//!
+//! - `max_errors = N`:
+//!
+//!   Report only the first `N` errors, the rest are replaced with a single
+//!   "M more error(s) suppressed" error. Protects the compiler from thousands of
+//!   errors emitted by a runaway loop. Unlimited by default.
+//!
+//! - `expr_context`:
+//!
+//!   A sequence of `compile_error!` invocations is not a valid expression, so a
+//!   function-like macro invoked in expression position (`let x = my_macro!();`)
+//!   would produce a syntax error on top of the real ones. With this setting on,
+//!   the errors (and the dummy, if any) are wrapped in a block: `{ compile_error!(...); dummy }`.
+//!   The dummy, if set, must be an expression then.
+//!
+//! - `summary`:
 //!
-//!     This setting is implied if `#[proc_macro_error]` is applied to a function
-//!     marked as `#[proc_macro]`, `#[proc_macro_derive]` or `#[proc_macro_attribute]`.
+//!   If more than one error was emitted, finish with one more error stating
+//!   the total count, e.g. "3 errors found", pointing to the call site.
+//!   Gives a quick sense of scope for macros reporting lots of errors.
 //!
-//!     This setting is also implied if `proc-macro-hack` was detected.
+//! - `single_line`:
+//!
+//!   Keep every generated `compile_error!` message on a single line: the lines of
+//!   the message and its notes are joined with `; `, e.g. `msg; = note: some note`.
+//!   For tools that mangle newlines embedded in the messages. Off by default,
+//!   the multi-line rendering is nicer. Has no effect on nightly.
+//!
+//! - `emit_on_panic`:
+//!
+//!   Turn an unrelated panic (an `expect()` deep in parsing, say) into a
+//!   "proc macro panicked" error carrying the panic message, displayed along with
+//!   the errors emitted before it and followed by the dummy, if any. Without this
+//!   setting the panic is propagated and the errors end up in its message.
 //!
 //! ## Macros
 //!
@@ -129,45 +252,78 @@
 //!   for details.
 //! * [`proc_macro::Span`]
 //! * [`proc-macro2::Span`]
+//! * [`SpanRange`](struct.SpanRange.html) - an explicit `first..last` range of spans,
+//!   for when the tokens aren't at hand anymore.
 //!
 //! The rest is your message in format-like style.
 //!
+//! When building a [`Diagnostic`] by hand, [`Diagnostic::with_spans_from`] gives you
+//! the same multi-token underline from a `syn` node.
+//!
 //! See [the next section](#syntax-1) for detailed syntax.
 //!
 //! - [`abort!`]:
 //!
-//!     Very much panic-like usage - abort right away and show the error.
-//!     Expands to [`!`] (never type).
+//!   Very much panic-like usage - abort right away and show the error.
+//!   Expands to [`!`] (never type).
 //!
 //! - [`abort_call_site!`]:
 //!
-//!     Shortcut for `abort!(Span::call_site(), ...)`. Expands to [`!`] (never type).
+//!   Shortcut for `abort!(Span::call_site(), ...)`. Expands to [`!`] (never type).
+//!
+//! - [`abort_with_dummy!`]:
+//!
+//!   Shortcut for `set_dummy(dummy); abort!(...)`, the syntax is
+//!   `abort_with_dummy!(dummy; abort args...)`. Expands to [`!`] (never type).
 //!
 //! - [`emit_error!`]:
 //!
-//!     [`proc_macro::Diagnostic`]-like usage - emit the error but keep going,
-//!     looking for other errors to report.
-//!     The compilation will fail nonetheless. Expands to [`()`] (unit type).
+//!   [`proc_macro::Diagnostic`]-like usage - emit the error but keep going,
+//!   looking for other errors to report.
+//!   The compilation will fail nonetheless. Expands to [`()`] (unit type).
 //!
 //! - [`emit_call_site_error!`]:
 //!
-//!     Shortcut for `emit_error!(Span::call_site(), ...)`. Expands to [`()`] (unit type).
+//!   Shortcut for `emit_error!(Span::call_site(), ...)`. Expands to [`()`] (unit type).
 //!
 //! - [`emit_warning!`]:
 //!
-//!     Like `emit_error!` but emit a warning instead of error. The compilation won't fail
-//!     because of warnings.
-//!     Expands to [`()`] (unit type).
+//!   Like `emit_error!` but emit a warning instead of error. The compilation won't fail
+//!   because of warnings.
+//!   Expands to [`()`] (unit type).
 //!
-//!     **Beware**: warnings are nightly only, they are completely ignored on stable.
+//!   **Beware**: warnings are nightly only, they are completely ignored on stable.
 //!
 //! - [`emit_call_site_warning!`]:
 //!
-//!     Shortcut for `emit_warning!(Span::call_site(), ...)`. Expands to [`()`] (unit type).
+//!   Shortcut for `emit_warning!(Span::call_site(), ...)`. Expands to [`()`] (unit type).
+//!
+//! - [`emit_note!`]:
+//!
+//!   Like `emit_warning!` but emit a standalone note, e.g. to explain what the macro
+//!   generated. Nightly only as well. Expands to [`()`] (unit type).
+//!
+//! - [`emit_call_site_note!`]:
+//!
+//!   Shortcut for `emit_note!(Span::call_site(), ...)`. Expands to [`()`] (unit type).
+//!
+//! - [`emit_errors!`]:
+//!
+//!   Emit every diagnostic from an iterable of `Diagnostic`s (or anything that
+//!   converts into `Diagnostic`, like `syn::Error`). Expands to [`()`] (unit type).
+//!
+//! - [`abort_all!`]:
+//!
+//!   Like `emit_errors!`, but aborts right after. Expands to [`!`] (never type).
 //!
 //! - [`diagnostic`]:
 //!
-//!     Build an instance of `Diagnostic` in format-like style.
+//!   Build an instance of `Diagnostic` in format-like style.
+//!
+//! - [`warning!`]:
+//!
+//!   Shortcut for `diagnostic!(span, Level::Warning, ...)`: build a warning
+//!   and decide later whether to emit it.
 //!
 //! #### Syntax
 //!
@@ -185,6 +341,10 @@
 //!
 //!     The second argument is the error message, it must implement [`ToString`].
 //!
+//!     A lone string literal is a format string (on Rust 1.32+), just like in `format!`:
+//!     ``abort!(span, "unknown field `{name}`")`` captures the `name` variable (Rust 1.58+),
+//!     and literal braces must be doubled: ``abort!(span, "expected `{{`")``.
+//!
 //! 3.  ```ignore
 //!     abort!(span, format_literal, format_args...)
 //!     ```
@@ -192,15 +352,22 @@
 //!     This form is pretty much the same as 2, except `format!(format_literal, format_args...)`
 //!     will be used to for the message instead of [`ToString`].
 //!
-//! That's it. `abort!`, `emit_warning`, `emit_error` share this exact syntax.
+//! That's it. `abort!`, `emit_warning`, `emit_note`, `emit_error` share this exact syntax.
 //!
-//! `abort_call_site!`, `emit_call_site_warning`, `emit_call_site_error` lack 1 form
-//! and do not take span in 2'th and 3'th forms. Those are essentially shortcuts for
-//! `macro!(Span::call_site(), args...)`.
+//! `abort_call_site!`, `emit_call_site_warning`, `emit_call_site_note`, `emit_call_site_error`
+//! do not take span in 2'th and 3'th forms. Those are essentially shortcuts for
+//! `macro!(Span::call_site(), args...)`. `abort_call_site!` and `emit_call_site_error`
+//! also accept a single `Diagnostic` (1 form), it keeps its own span; any other
+//! single argument is a message (2 form).
 //!
 //! `diagnostic!` requires a [`Level`] instance between `span` and second argument
 //! (1'th form is the same).
 //!
+//! `abort!` also takes an explicit level as `abort!(span, level = Level::Warning, ...)`,
+//! followed by 2 or 3 form. Aborting on a warning is unusual but supported: the
+//! diagnostic is labeled as a warning, the macro execution stops nonetheless (and
+//! just like [`Diagnostic::abort`] notes, warnings are not displayed on stable).
+//!
 //! > **Important!**
 //! >
 //! > If you have some type from `proc_macro` or `syn` to point to, do not call `.span()`
@@ -230,8 +397,10 @@
 //!       note = "to_string"; // <--- one arg uses `.to_string()` instead of `format!()`
 //!
 //!       yay = "I see what {} did here", "you"; // <--- "help =" and "hint =" are mapped
-//!                                              // to Diagnostic::help,
-//!                                              // anything else is Diagnostic::note
+//!                                              // to Diagnostic::help, "note =" is
+//!                                              // Diagnostic::note, anything else is
+//!                                              // a note rendered as "= yay: ..."
+//!                                              // ("= note: yay: ..." on nightly)
 //!
 //!       wow = note_span => "custom span"; // <--- attachments can have their own span
 //!                                         //      it takes effect only on nightly though
@@ -239,11 +408,22 @@
 //!       hint =? opt_help; // <-- "optional" attachment, get displayed only if `Some`
 //!                         //     must be single `Option` expression
 //!
-//!       note =? note_span => opt_help // <-- optional attachments can have custom spans too
+//!       note =? note_span => opt_help; // <-- optional attachments can have custom spans too
+//!
+//!       help =?? || compute_hint(); // <-- "lazy" optional attachment, the closure
+//!                                   //     must return `Option`, it's called once
+//!                                   //     when the diagnostic is built
+//!
+//!       note =?? note_span => || compute_hint(); // <-- with custom span
+//!
+//!       code = "E0042" // <-- "code =" is not a note, it sets the error code,
+//!                      //     see Diagnostic::code
 //!   );
 //!   ```
 //!
-
+//!   `diagnostic!` can attach notes to the converted single expression as well,
+//!   it must be prefixed with `from` then: `diagnostic!(from err; help = "...")`.
+//!
 //! ### Diagnostic type
 //!
 //! [`Diagnostic`] type is intentionally designed to be API compatible with [`proc_macro::Diagnostic`].
@@ -256,8 +436,15 @@
 //! [`emit_error!`]: macro.emit_error.html
 //! [`emit_call_site_warning!`]: macro.emit_call_site_error.html
 //! [`emit_call_site_error!`]: macro.emit_call_site_warning.html
+//! [`emit_note!`]: macro.emit_note.html
+//! [`emit_call_site_note!`]: macro.emit_call_site_note.html
+//! [`emit_errors!`]: macro.emit_errors.html
+//! [`abort_all!`]: macro.abort_all.html
+//! [`abort_with_dummy!`]: macro.abort_with_dummy.html
 //! [`diagnostic!`]: macro.diagnostic.html
+//! [`warning!`]: macro.warning.html
 //! [`Diagnostic`]: struct.Diagnostic.html
+//! [`Diagnostic::abort`]: struct.Diagnostic.html#method.abort
 //!
 //! [`proc_macro::Span`]: https://doc.rust-lang.org/proc_macro/struct.Span.html
 //! [`proc_macro::Diagnostic`]: https://doc.rust-lang.org/proc_macro/struct.Diagnostic.html
@@ -278,19 +465,28 @@
 extern crate proc_macro;
 
 pub use crate::{
-    diagnostic::{Diagnostic, DiagnosticExt, Level},
-    dummy::{append_dummy, set_dummy},
+    diagnostic::{Diagnostic, DiagnosticExt, Level, ParseLevelError},
+    dummy::{add_dummy, append_dummy, clear_dummy, set_dummy},
 };
 pub use proc_macro_error_attr::proc_macro_error;
 
-use proc_macro2::Span;
+use crate::diagnostic::DiagnosticKey;
+
+use proc_macro2::{Delimiter, Group, Span, TokenStream, TokenTree};
 use quote::{quote, ToTokens};
 
-use std::cell::Cell;
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::collections::HashSet;
+use std::fmt::Display;
 use std::panic::{catch_unwind, resume_unwind, UnwindSafe};
+use std::rc::Rc;
 
 pub mod dummy;
 
+#[cfg(feature = "testing")]
+pub mod testing;
+
 mod diagnostic;
 mod macros;
 mod sealed;
@@ -303,6 +499,14 @@ mod imp;
 #[path = "imp/delegate.rs"]
 mod imp;
 
+/// A range of spans: from the start of `first` to the end of `last`.
+///
+/// On stable, a diagnostic can't point to a multi-token range with a single span,
+/// so the range is preserved explicitly. It can be passed as the span argument
+/// of [`abort!`], [`emit_error!`] and friends directly.
+///
+/// [`abort!`]: macro.abort.html
+/// [`emit_error!`]: macro.emit_error.html
 #[derive(Debug, Clone, Copy)]
 pub struct SpanRange {
     pub first: Span,
@@ -354,6 +558,37 @@ impl SpanRange {
     }
 }
 
+/// The call site range, see [`SpanRange::call_site`](#method.call_site).
+impl Default for SpanRange {
+    fn default() -> Self {
+        SpanRange::call_site()
+    }
+}
+
+impl From<Span> for SpanRange {
+    fn from(span: Span) -> Self {
+        SpanRange::single_span(span)
+    }
+}
+
+impl From<proc_macro::Span> for SpanRange {
+    fn from(span: proc_macro::Span) -> Self {
+        SpanRange::single_span(span.into())
+    }
+}
+
+impl From<(Span, Span)> for SpanRange {
+    fn from((first, last): (Span, Span)) -> Self {
+        SpanRange { first, last }
+    }
+}
+
+impl From<SpanRange> for (Span, Span) {
+    fn from(range: SpanRange) -> Self {
+        (range.first, range.last)
+    }
+}
+
 /// This traits expands `Result<T, Into<Diagnostic>>` with some handy shortcuts.
 pub trait ResultExt {
     type Ok;
@@ -366,25 +601,237 @@ pub trait ResultExt {
     /// otherwise abort macro execution via `abort!`.
     /// If it aborts then resulting error message will be preceded with `message`.
     fn expect_or_abort(self, msg: &str) -> Self::Ok;
+
+    /// Behaves like `Result::unwrap_or`: if self is `Ok` yield the contained value,
+    /// otherwise emit the error via `emit_error!` and yield `default`.
+    ///
+    /// The macro execution is not aborted, so you can keep looking for other errors.
+    fn emit_or(self, default: Self::Ok) -> Self::Ok;
+}
+
+/// This traits expands `Result<T, E: Display>` for errors that can't be turned
+/// into [`Diagnostic`] directly.
+///
+/// [`Diagnostic`]: struct.Diagnostic.html
+pub trait ResultDisplayExt {
+    type Ok;
+
+    /// Turn the error into an error [`Diagnostic`] pointing to `span`,
+    /// the message is the error's `Display` output.
+    ///
+    /// ```ignore
+    /// let value = some_lib::parse(&input)
+    ///     .map_err_to_diagnostic(lit.span())
+    ///     .unwrap_or_abort();
+    /// ```
+    ///
+    /// [`Diagnostic`]: struct.Diagnostic.html
+    #[allow(clippy::result_large_err)]
+    fn map_err_to_diagnostic(self, span: Span) -> Result<Self::Ok, Diagnostic>;
+}
+
+impl<T, E: Display> ResultDisplayExt for Result<T, E> {
+    type Ok = T;
+
+    fn map_err_to_diagnostic(self, span: Span) -> Result<T, Diagnostic> {
+        self.map_err(|e| Diagnostic::spanned(span, Level::Error, e.to_string()))
+    }
+}
+
+/// This traits expands `Result<T, Vec<Diagnostic>>`, for validation code
+/// that reports all the problems at once.
+pub trait ResultVecExt {
+    type Ok;
+
+    /// If self is `Ok` yield the contained value, otherwise emit every diagnostic
+    /// and abort macro execution.
+    ///
+    /// An empty vector aborts with a generic error pointing to the call site,
+    /// so the failure is not silent.
+    fn unwrap_or_abort_all(self) -> Self::Ok;
+}
+
+impl<T> ResultVecExt for Result<T, Vec<Diagnostic>> {
+    type Ok = T;
+
+    fn unwrap_or_abort_all(self) -> T {
+        match self {
+            Ok(res) => res,
+            Err(ref diags) if diags.is_empty() => {
+                abort_call_site!("called `unwrap_or_abort_all()` on an empty `Err` value")
+            }
+            Err(diags) => {
+                for diag in diags {
+                    diag.emit();
+                }
+                abort_now()
+            }
+        }
+    }
 }
 
 /// This traits expands `Option` with some handy shortcuts.
 pub trait OptionExt {
     type Some;
 
+    /// Behaves like `Option::unwrap`: if self is `Some` yield the contained value,
+    /// otherwise abort macro execution via `abort_call_site!` with a generic message.
+    fn unwrap_or_abort(self) -> Self::Some;
+
     /// Behaves like `Option::expect`: if self is `Some` yield the contained value,
     /// otherwise abort macro execution via `abort_call_site!`.
     /// If it aborts the `message` will be used for [`compile_error!`][compl_err] invocation.
     ///
     /// [compl_err]: https://doc.rust-lang.org/std/macro.compile_error.html
     fn expect_or_abort(self, msg: &str) -> Self::Some;
+
+    /// Behaves like `Option::ok_or_else`: if self is `Some` yield the contained value,
+    /// otherwise build a diagnostic via `f` and abort macro execution with it.
+    ///
+    /// Unlike `expect_or_abort`, this allows you to build a fully-featured
+    /// diagnostic with its own span and notes, and to do it lazily.
+    fn ok_or_abort<F>(self, f: F) -> Self::Some
+    where
+        F: FnOnce() -> Diagnostic;
+}
+
+/// Emit the diagnostic, the same as [`Diagnostic::emit`] but as a free function
+/// for iterator combinators: `diags.into_iter().for_each(proc_macro_error::emit)`.
+///
+/// [`Diagnostic::emit`]: struct.Diagnostic.html#method.emit
+pub fn emit(diag: Diagnostic) {
+    diag.emit()
+}
+
+/// Stop the macro now; call after emitting diagnostics.
+///
+/// All the emitted errors are displayed, just like with [`abort!`], but no new
+/// diagnostic is added. Handy for custom emission loops, see also [`abort_if_dirty`].
+///
+/// [`abort!`]: macro.abort.html
+/// [`abort_if_dirty`]: fn.abort_if_dirty.html
+pub fn abort() -> ! {
+    abort_now()
 }
 
 /// Abort macro execution and display all the emitted errors, if any.
 ///
 /// Does nothing if no errors were emitted (warnings do not count).
 pub fn abort_if_dirty() {
-    imp::abort_if_dirty();
+    if has_errors() {
+        abort_now()
+    }
+}
+
+/// Take the errors emitted so far out of the storage, so they won't be reported
+/// when the macro returns.
+///
+/// This is a low-level escape hatch for macros with unusual output layouts:
+/// [`Diagnostic`] implements `ToTokens`, so the errors can be placed anywhere in
+/// the output manually.
+///
+/// Always empty on nightly, the errors are reported right away there.
+///
+/// [`Diagnostic`]: struct.Diagnostic.html
+pub fn drain_errors() -> Vec<Diagnostic> {
+    check_correctness();
+    imp::drain_errors()
+}
+
+/// Check whether any errors were emitted so far (warnings do not count).
+///
+/// Unlike [`abort_if_dirty`], this function never aborts, so you can use it
+/// to branch on the error state, e.g. skip expensive code generation.
+///
+/// [`abort_if_dirty`]: fn.abort_if_dirty.html
+pub fn has_errors() -> bool {
+    error_count() != 0
+}
+
+/// Whether the diagnostics are reported via the nightly-only `proc_macro::Diagnostic` API.
+///
+/// If `false`, the stable fallback is in use: warnings are ignored, and spans of
+/// notes and help messages are collapsed to the span of the main message.
+/// See [limitations](index.html#limitations).
+pub fn is_nightly() -> bool {
+    IS_NIGHTLY
+}
+
+#[cfg(use_fallback)]
+const IS_NIGHTLY: bool = false;
+
+#[cfg(not(use_fallback))]
+const IS_NIGHTLY: bool = true;
+
+/// Set a hook that will be called for every emitted diagnostic (warnings included),
+/// right before the diagnostic is recorded/reported as usual.
+///
+/// This allows you to observe the diagnostics, e.g. to forward them elsewhere.
+/// The hook is thread-local and stays in effect until [`clear_emit_hook`] is called,
+/// setting a new hook replaces the old one.
+///
+/// [`clear_emit_hook`]: fn.clear_emit_hook.html
+pub fn set_emit_hook<F>(hook: F)
+where
+    F: Fn(&Diagnostic) + 'static,
+{
+    EMIT_HOOK.with(|h| h.replace(Some(Rc::new(hook))));
+}
+
+/// Remove the hook set by [`set_emit_hook`], if any.
+///
+/// [`set_emit_hook`]: fn.set_emit_hook.html
+pub fn clear_emit_hook() {
+    EMIT_HOOK.with(|h| h.replace(None));
+}
+
+type EmitHook = Rc<dyn Fn(&Diagnostic)>;
+
+const DEFAULT_NOTE_PREFIX: &str = "  = ";
+
+/// Set the marker notes start with in `compile_error!` messages and
+/// [`Diagnostic::render`], `"  = "` (rustc style) by default: `  = note: msg`.
+///
+/// Meant for tools parsing the messages with their own conventions.
+/// The prefix is thread-local and stays in effect until set again.
+///
+/// [`Diagnostic::render`]: struct.Diagnostic.html#method.render
+pub fn set_note_prefix(prefix: &'static str) {
+    NOTE_PREFIX.with(|p| p.set(prefix));
+}
+
+fn note_prefix() -> &'static str {
+    NOTE_PREFIX.with(|p| p.get())
+}
+
+fn call_emit_hook(diag: &Diagnostic) {
+    // the hook is cloned out so it can (re)set hooks or emit diagnostics itself
+    if let Some(hook) = EMIT_HOOK.with(|h| h.borrow().clone()) {
+        hook(diag);
+    }
+}
+
+/// The number of errors emitted so far (warnings do not count).
+pub fn error_count() -> usize {
+    let count = imp::error_count();
+
+    #[cfg(feature = "testing")]
+    let count = count + testing::error_count();
+
+    count
+}
+
+/// The number of warnings emitted so far.
+///
+/// Warnings are counted on stable as well, even though they are not displayed there.
+/// Warnings turned into errors by the `warn_as_error` setting count as errors.
+pub fn warning_count() -> usize {
+    let count = imp::warning_count();
+
+    #[cfg(feature = "testing")]
+    let count = count + testing::warning_count();
+
+    count
 }
 
 impl<T, E: Into<Diagnostic>> ResultExt for Result<T, E> {
@@ -402,71 +849,200 @@ impl<T, E: Into<Diagnostic>> ResultExt for Result<T, E> {
             Ok(res) => res,
             Err(e) => {
                 let mut e = e.into();
-                e.msg = format!("{}: {}", message, e.msg);
+                e.prepend_message(&format!("{}: ", message));
                 e.abort()
             }
         }
     }
+
+    fn emit_or(self, default: T) -> T {
+        match self {
+            Ok(res) => res,
+            Err(e) => {
+                e.into().emit();
+                default
+            }
+        }
+    }
 }
 
 impl<T> OptionExt for Option<T> {
     type Some = T;
 
+    fn unwrap_or_abort(self) -> T {
+        match self {
+            Some(res) => res,
+            None => abort_call_site!("called `unwrap_or_abort()` on a `None` value"),
+        }
+    }
+
     fn expect_or_abort(self, message: &str) -> T {
         match self {
             Some(res) => res,
             None => abort_call_site!(message),
         }
     }
+
+    fn ok_or_abort<F>(self, f: F) -> T
+    where
+        F: FnOnce() -> Diagnostic,
+    {
+        match self {
+            Some(res) => res,
+            None => f().abort(),
+        }
+    }
+}
+
+/// The manual equivalent of plain `#[proc_macro_error]`: performs the setup,
+/// runs `f` and turns the errors emitted inside into `compile_error!` invocations
+/// (or real diagnostics on nightly).
+///
+/// Use it if you prefer explicit wrapping over the attribute:
+///
+/// ```no_run
+/// # extern crate proc_macro;
+/// use proc_macro::TokenStream;
+/// use proc_macro_error::{abort_call_site, catch};
+///
+/// # const IGNORE: &str = stringify! {
+/// #[proc_macro]
+/// # };
+/// pub fn my_macro(input: TokenStream) -> TokenStream {
+///     catch(|| {
+///         if input.is_empty() {
+///             abort_call_site!("expected some input");
+///         }
+///         input
+///     })
+/// }
+/// ```
+///
+/// Unlike the attribute, it doesn't support any settings.
+pub fn catch<F>(f: F) -> proc_macro::TokenStream
+where
+    F: FnOnce() -> proc_macro::TokenStream + UnwindSafe,
+{
+    entry_point(f, EntryPointSettings::default())
+}
+
+/// Settings of [`entry_point`], correspond to `#[proc_macro_error(...)]` settings.
+///
+/// **NOT PUBLIC API, SUBJECT TO CHANGE WITHOUT ANY NOTICE**
+#[doc(hidden)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EntryPointSettings {
+    pub proc_macro_hack: bool,
+    pub dedup: bool,
+    pub warn_as_error: bool,
+    pub expr_context: bool,
+    pub single_line: bool,
+    pub summary: bool,
+    pub emit_on_panic: bool,
+    pub max_errors: Option<usize>,
 }
 
 /// This is the entry point for a proc-macro.
 ///
+/// `T` is either `proc_macro::TokenStream` or `proc_macro2::TokenStream`,
+/// the latter is for helpers wrapped via `#[proc_macro_error(allow_not_macro)]`.
+///
 /// **NOT PUBLIC API, SUBJECT TO CHANGE WITHOUT ANY NOTICE**
 #[doc(hidden)]
-pub fn entry_point<F>(f: F, proc_macro_hack: bool) -> proc_macro::TokenStream
+pub fn entry_point<F, T>(f: F, settings: EntryPointSettings) -> T
 where
-    F: FnOnce() -> proc_macro::TokenStream + UnwindSafe,
+    F: FnOnce() -> T + UnwindSafe,
+    T: From<TokenStream>,
 {
+    // A nested entry point (a wrapped helper called from a macro) starts with a clean
+    // slate, the errors and the dummy of the enclosing one are put back on exit.
     ENTERED_ENTRY_POINT.with(|flag| flag.set(flag.get() + 1));
+    let old_settings = SETTINGS.with(|s| s.replace(settings));
+    let old_emitted = EMITTED.with(|emitted| emitted.replace(HashSet::new()));
+    let old_state = imp::save_state();
+    let old_dummy = dummy::replace(None);
+
     let caught = catch_unwind(f);
     let dummy = dummy::cleanup();
     let err_storage = imp::cleanup();
-    ENTERED_ENTRY_POINT.with(|flag| flag.set(flag.get() - 1));
 
-    let gen_error = || {
-        if proc_macro_hack {
-            quote! {{
-                macro_rules! proc_macro_call {
-                    () => ( unimplemented!() )
-                }
-
-                #(#err_storage)*
-                #dummy
-
-                unimplemented!()
-            }}
-        } else {
-            quote!( #(#err_storage)* #dummy )
-        }
-    };
+    dummy::replace(old_dummy);
+    imp::restore_state(old_state);
+    EMITTED.with(|emitted| emitted.replace(old_emitted));
+    SETTINGS.with(|s| s.set(old_settings));
+    // `testing::reset_state` may have zeroed it already
+    ENTERED_ENTRY_POINT.with(|flag| flag.set(flag.get().saturating_sub(1)));
 
     match caught {
         Ok(ts) => {
             if err_storage.is_empty() {
                 ts
             } else {
-                gen_error().into()
+                gen_error(err_storage, dummy, settings).into()
             }
         }
 
         Err(boxed) => match boxed.downcast::<AbortNow>() {
-            Ok(_) => gen_error().into(),
-            Err(boxed) => resume_unwind(boxed),
+            Ok(_) => gen_error(err_storage, dummy, settings).into(),
+            Err(boxed) => {
+                if settings.emit_on_panic {
+                    let mut err_storage = err_storage;
+                    err_storage.push(panic_diagnostic(&*boxed));
+                    gen_error(err_storage, dummy, settings).into()
+                } else {
+                    resume_unwind(with_lost_errors(boxed, &err_storage))
+                }
+            }
         },
     }
 }
 
+/// An unrelated panic is propagated as is, but the errors emitted before it
+/// would be lost then. Append them to the panic message, the compiler displays it.
+///
+/// The payload keeps its type, `&'static str` or `String`. Payloads of other types
+/// carry no message to append to, they are returned intact.
+fn with_lost_errors(payload: Box<dyn Any + Send>, errors: &[Diagnostic]) -> Box<dyn Any + Send> {
+    if errors.is_empty() {
+        return payload;
+    }
+
+    let rendered = errors
+        .iter()
+        .map(Diagnostic::render)
+        .collect::<Vec<_>>()
+        .join("\n");
+    let append = |msg: &str| format!("{}\n\nerrors emitted before the panic:\n{}", msg, rendered);
+
+    if let Some(msg) = payload.downcast_ref::<&'static str>() {
+        // leaked once per panic, which ends the macro anyway
+        let msg: &'static str = Box::leak(append(msg).into_boxed_str());
+        return Box::new(msg);
+    }
+
+    match payload.downcast::<String>() {
+        Ok(msg) => Box::new(append(&msg)),
+        Err(payload) => payload,
+    }
+}
+
+/// The `emit_on_panic` setting: the panic becomes an error of its own,
+/// emitted after the errors recorded before it.
+fn panic_diagnostic(payload: &(dyn Any + Send)) -> Diagnostic {
+    let diag = Diagnostic::new(Level::Error, "proc macro panicked".to_string());
+    match panic_message(payload) {
+        Some(msg) => diag.help(format!("message: {}", msg)),
+        None => diag,
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> Option<String> {
+    match payload.downcast_ref::<&str>() {
+        Some(msg) => Some(msg.to_string()),
+        None => payload.downcast_ref::<String>().cloned(),
+    }
+}
+
 fn abort_now() -> ! {
     check_correctness();
     panic!(AbortNow)
@@ -474,19 +1050,128 @@ fn abort_now() -> ! {
 
 thread_local! {
     static ENTERED_ENTRY_POINT: Cell<usize> = Cell::new(0);
+    static SETTINGS: Cell<EntryPointSettings> = Cell::new(EntryPointSettings::default());
+    static EMITTED: RefCell<HashSet<DiagnosticKey>> = RefCell::new(HashSet::new());
+    static EMIT_HOOK: RefCell<Option<EmitHook>> = RefCell::new(None);
+    static NOTE_PREFIX: Cell<&'static str> = Cell::new(DEFAULT_NOTE_PREFIX);
+}
+
+#[cfg(feature = "testing")]
+fn reset_entry_point_state() {
+    SETTINGS.with(|s| s.set(EntryPointSettings::default()));
+    EMITTED.with(|emitted| emitted.borrow_mut().clear());
+}
+
+/// The `max_errors = N` setting.
+fn max_errors() -> Option<usize> {
+    SETTINGS.with(|s| s.get().max_errors)
+}
+
+/// The error replacing the ones cut by `max_errors = N`.
+fn suppressed_message(count: usize) -> String {
+    if count == 1 {
+        "1 more error suppressed".to_string()
+    } else {
+        format!("{} more errors suppressed", count)
+    }
+}
+
+/// Whether the `summary` setting is on.
+fn summary() -> bool {
+    SETTINGS.with(|s| s.get().summary)
+}
+
+/// Whether the `single_line` setting is on.
+fn single_line() -> bool {
+    SETTINGS.with(|s| s.get().single_line)
+}
+
+/// Whether the `warn_as_error` setting is on.
+fn warn_as_error() -> bool {
+    SETTINGS.with(|s| s.get().warn_as_error)
+}
+
+/// Check if an identical diagnostic has already been emitted and remember this one.
+/// Always `false` unless the `dedup` setting is on.
+fn is_duplicate(diag: &Diagnostic) -> bool {
+    if !SETTINGS.with(|s| s.get().dedup) {
+        return false;
+    }
+
+    let key = DiagnosticKey::new(diag);
+    EMITTED.with(|emitted| !emitted.borrow_mut().insert(key))
 }
 
 struct AbortNow;
 
+fn gen_error(
+    err_storage: Vec<Diagnostic>,
+    dummy: Option<TokenStream>,
+    settings: EntryPointSettings,
+) -> TokenStream {
+    // The tokens are moved into a single stream: the dummy may well contain
+    // (a modified copy of) the whole input, interpolating it via `quote!` would clone it.
+    let mut ts = TokenStream::new();
+
+    // goes first so the dummy can use it
+    if settings.proc_macro_hack {
+        ts.extend(quote! {
+            macro_rules! proc_macro_call {
+                () => ( unimplemented!() )
+            }
+        });
+    }
+
+    for diag in &err_storage {
+        diag.to_tokens_impl(&mut ts, settings.single_line);
+    }
+    ts.extend(dummy);
+
+    if settings.proc_macro_hack {
+        ts.extend(quote!(unimplemented!()));
+    }
+
+    if settings.proc_macro_hack || settings.expr_context {
+        TokenTree::Group(Group::new(Delimiter::Brace, ts)).into()
+    } else {
+        ts
+    }
+}
+
 fn check_correctness() {
     if ENTERED_ENTRY_POINT.with(|flag| flag.get()) == 0 {
+        // No `#[proc_macro_error]` function is on the stack, so there's no attribute
+        // to take the name from. The invocation of the running macro names it, though.
+        let culprit = match invocation_text() {
+            Some(invocation) => format!("the function behind `{}`", invocation),
+            None => "your #[proc_macro] function".to_string(),
+        };
         panic!(
             "proc-macro-error API cannot be used outside of `entry_point` invocation, \
-             perhaps you forgot to annotate your #[proc_macro] function with `#[proc_macro_error]"
+             perhaps you forgot to annotate {} with `#[proc_macro_error]`",
+            culprit
         );
     }
 }
 
+/// The beginning of the source text of the running macro's invocation,
+/// `None` outside of a proc-macro or if the compiler can't provide it.
+fn invocation_text() -> Option<String> {
+    const MAX_LEN: usize = 40;
+
+    let text = Span::call_site().source_text()?;
+    let line = text.lines().next().unwrap_or("").trim();
+    if line.is_empty() {
+        return None;
+    }
+
+    let mut res = line.chars().take(MAX_LEN).collect::<String>();
+    if res.len() < text.trim().len() {
+        res.push_str("...");
+    }
+    Some(res)
+}
+
 /// **ALL THE STUFF INSIDE IS NOT PUBLIC API!!!**
 #[doc(hidden)]
 pub mod __export {
@@ -497,7 +1182,13 @@ pub mod __export {
     use proc_macro2::Span;
     use quote::ToTokens;
 
-    use crate::SpanRange;
+    use std::fmt::Display;
+
+    use crate::{Diagnostic, Level, SpanRange};
+
+    pub fn abort_now() -> ! {
+        crate::abort_now()
+    }
 
     // inspired by
     // https://github.com/dtolnay/case-studies/blob/master/autoref-specialization/README.md#simple-application
@@ -524,13 +1215,7 @@ pub mod __export {
 
     impl<T: ToTokens> ToTokensAsSpanRange for &T {
         fn FIRST_ARG_MUST_EITHER_BE_Span_OR_IMPLEMENT_ToTokens_OR_BE_SpanRange(&self) -> SpanRange {
-            let mut ts = self.to_token_stream().into_iter();
-            let first = ts
-                .next()
-                .map(|tt| tt.span())
-                .unwrap_or_else(Span::call_site);
-            let last = ts.last().map(|tt| tt.span()).unwrap_or(first);
-            SpanRange { first, last }
+            SpanRange::from_tokens(*self)
         }
     }
 
@@ -557,4 +1242,28 @@ pub mod __export {
             *self
         }
     }
+
+    // Single argument of `abort_call_site!` and alike: a `Diagnostic` is taken
+    // by value as is, anything else is a message and is only borrowed, just like
+    // `$msg.to_string()` in the message form does.
+
+    pub trait DiagnosticAsCallSite {
+        fn into_call_site_diagnostic(self, level: Level) -> Diagnostic;
+    }
+
+    pub trait DisplayAsCallSite {
+        fn into_call_site_diagnostic(self, level: Level) -> Diagnostic;
+    }
+
+    impl DiagnosticAsCallSite for Diagnostic {
+        fn into_call_site_diagnostic(self, _level: Level) -> Diagnostic {
+            self
+        }
+    }
+
+    impl<T: Display + ?Sized> DisplayAsCallSite for &T {
+        fn into_call_site_diagnostic(self, level: Level) -> Diagnostic {
+            Diagnostic::new(level, self.to_string())
+        }
+    }
 }
@@ -7,8 +7,17 @@
 ///
 /// See [the guide](index.html#guide).
 ///
+/// The single expression form can have notes attached too, it's spelled
+/// `diagnostic!(from err; help = "...")` then.
 #[macro_export]
 macro_rules! diagnostic {
+    // from alias, with notes
+    (from $err:expr ; $($rest:tt)+) => {{
+        let diag = $crate::Diagnostic::from($err);
+        $crate::__pme__suggestions!(diag $($rest)*);
+        diag
+    }};
+
     // from alias
     ($err:expr) => { $crate::Diagnostic::from($err) };
 
@@ -21,7 +30,6 @@ macro_rules! diagnostic {
             SpanAsSpanRange,
             SpanRangeAsSpanRange
         };
-        use $crate::DiagnosticExt;
         let span_range = (&$span).FIRST_ARG_MUST_EITHER_BE_Span_OR_IMPLEMENT_ToTokens_OR_BE_SpanRange();
 
         let diag = $crate::Diagnostic::spanned_range(
@@ -33,6 +41,10 @@ macro_rules! diagnostic {
         diag
     }};
 
+    ($span:expr, $level:expr, $msg:tt ; $($rest:tt)+) => {
+        $crate::diagnostic!($span, $level, $crate::__pme__format_lone!($msg) ; $($rest)*)
+    };
+
     ($span:expr, $level:expr, $msg:expr ; $($rest:tt)+) => {{
         #[allow(unused_imports)]
         use $crate::__export::{
@@ -41,7 +53,6 @@ macro_rules! diagnostic {
             SpanAsSpanRange,
             SpanRangeAsSpanRange
         };
-        use $crate::DiagnosticExt;
         let span_range = (&$span).FIRST_ARG_MUST_EITHER_BE_Span_OR_IMPLEMENT_ToTokens_OR_BE_SpanRange();
 
         let diag = $crate::Diagnostic::spanned_range(span_range, $level, $msg.to_string());
@@ -58,7 +69,6 @@ macro_rules! diagnostic {
             SpanAsSpanRange,
             SpanRangeAsSpanRange
         };
-        use $crate::DiagnosticExt;
         let span_range = (&$span).FIRST_ARG_MUST_EITHER_BE_Span_OR_IMPLEMENT_ToTokens_OR_BE_SpanRange();
 
         $crate::Diagnostic::spanned_range(
@@ -68,6 +78,10 @@ macro_rules! diagnostic {
         )
     }};
 
+    ($span:expr, $level:expr, $msg:tt) => {
+        $crate::diagnostic!($span, $level, $crate::__pme__format_lone!($msg))
+    };
+
     ($span:expr, $level:expr, $msg:expr) => {{
         #[allow(unused_imports)]
         use $crate::__export::{
@@ -76,7 +90,6 @@ macro_rules! diagnostic {
             SpanAsSpanRange,
             SpanRangeAsSpanRange
         };
-        use $crate::DiagnosticExt;
         let span_range = (&$span).FIRST_ARG_MUST_EITHER_BE_Span_OR_IMPLEMENT_ToTokens_OR_BE_SpanRange();
 
         $crate::Diagnostic::spanned_range(span_range, $level, $msg.to_string())
@@ -88,34 +101,82 @@ macro_rules! diagnostic {
     ($span:expr, $level:expr, $fmt:expr, $($args:expr),+, ; $($rest:tt)+) => {
         $crate::diagnostic!($span, $level, $fmt, $($args),* ; $($rest)*)
     };
+    ($span:expr, $level:expr, $msg:tt, ; $($rest:tt)+) => {
+        $crate::diagnostic!($span, $level, $msg ; $($rest)*)
+    };
     ($span:expr, $level:expr, $msg:expr, ; $($rest:tt)+) => {
         $crate::diagnostic!($span, $level, $msg ; $($rest)*)
     };
     ($span:expr, $level:expr, $fmt:expr, $($args:expr),+,) => {
         $crate::diagnostic!($span, $level, $fmt, $($args),*)
     };
+    ($span:expr, $level:expr, $msg:tt,) => {
+        $crate::diagnostic!($span, $level, $msg)
+    };
     ($span:expr, $level:expr, $msg:expr,) => {
         $crate::diagnostic!($span, $level, $msg)
     };
     // ($err:expr,) => { $crate::diagnostic!($err) };
 }
 
+/// Build a warning [`Diagnostic`](struct.Diagnostic.html) without emitting it,
+/// a shortcut for `diagnostic!(span, Level::Warning, ...)`.
+///
+/// # Syntax
+///
+/// Same as [`emit_warning!`](macro.emit_warning.html), see [the guide](index.html#guide).
+///
+/// ```ignore
+/// let warning = warning!(span, "deprecated attribute"; note = "use `bar` instead");
+/// if !quiet {
+///     warning.emit();
+/// }
+/// ```
+#[macro_export]
+macro_rules! warning {
+    ($span:expr, $($tts:tt)*) => {
+        $crate::diagnostic!($span, $crate::Level::Warning, $($tts)*)
+    };
+}
+
 /// Abort proc-macro execution right now and display the error.
 ///
 /// # Syntax
 ///
 /// See [the guide](index.html#guide).
+///
+/// The level can be set explicitly, `abort!(span, level = Level::Warning, msg...)`.
 #[macro_export]
 macro_rules! abort {
     ($err:expr) => {
         $crate::diagnostic!($err).abort()
     };
 
+    ($span:expr, level = $level:expr, $($tts:tt)*) => {
+        $crate::diagnostic!($span, $level, $($tts)*).abort()
+    };
+
     ($span:expr, $($tts:tt)*) => {
         $crate::diagnostic!($span, $crate::Level::Error, $($tts)*).abort()
     };
 }
 
+/// Set the dummy and abort right away: `abort_with_dummy!(dummy; span, msg...)`
+/// is a shortcut for `set_dummy(dummy); abort!(span, msg...)`.
+///
+/// # Syntax
+///
+/// Everything after `;` is passed to [`abort!`](macro.abort.html) as is,
+/// see [the guide](index.html#guide).
+///
+#[macro_export]
+macro_rules! abort_with_dummy {
+    ($dummy:expr; $($tts:tt)*) => {{
+        $crate::set_dummy($dummy);
+        $crate::abort!($($tts)*)
+    }};
+}
+
 /// Shortcut for `abort!(Span::call_site(), msg...)`. This macro
 /// is still preferable over plain panic, panics are not for error reporting.
 ///
@@ -123,8 +184,25 @@ macro_rules! abort {
 ///
 /// See [the guide](index.html#guide).
 ///
+/// A single [`Diagnostic`](struct.Diagnostic.html) is aborted with as is,
+/// it keeps its own span: `abort_call_site!(diag)`.
+///
 #[macro_export]
 macro_rules! abort_call_site {
+    ($err:tt) => {{
+        #[allow(unused_imports)]
+        use $crate::__export::{DiagnosticAsCallSite, DisplayAsCallSite};
+        $crate::__pme__format_lone!($err)
+            .into_call_site_diagnostic($crate::Level::Error)
+            .abort()
+    }};
+
+    ($err:expr) => {{
+        #[allow(unused_imports)]
+        use $crate::__export::{DiagnosticAsCallSite, DisplayAsCallSite};
+        $err.into_call_site_diagnostic($crate::Level::Error).abort()
+    }};
+
     ($($tts:tt)*) => {
         $crate::abort!($crate::__export::proc_macro2::Span::call_site(), $($tts)*)
     };
@@ -155,8 +233,25 @@ macro_rules! emit_error {
 ///
 /// See [the guide](index.html#guide).
 ///
+/// A single [`Diagnostic`](struct.Diagnostic.html) is emitted as is,
+/// it keeps its own span: `emit_call_site_error!(diag)`.
+///
 #[macro_export]
 macro_rules! emit_call_site_error {
+    ($err:tt) => {{
+        #[allow(unused_imports)]
+        use $crate::__export::{DiagnosticAsCallSite, DisplayAsCallSite};
+        $crate::__pme__format_lone!($err)
+            .into_call_site_diagnostic($crate::Level::Error)
+            .emit()
+    }};
+
+    ($err:expr) => {{
+        #[allow(unused_imports)]
+        use $crate::__export::{DiagnosticAsCallSite, DisplayAsCallSite};
+        $err.into_call_site_diagnostic($crate::Level::Error).emit()
+    }};
+
     ($($tts:tt)*) => {
         $crate::emit_error!($crate::__export::proc_macro2::Span::call_site(), $($tts)*)
     };
@@ -192,11 +287,114 @@ macro_rules! emit_call_site_warning {
     }};
 }
 
+/// Emit a note, a standalone informational message. Like warnings,
+/// notes don't fail the compilation.
+///
+/// **Does nothing on stable**
+///
+/// # Syntax
+///
+/// See [the guide](index.html#guide).
+///
+#[macro_export]
+macro_rules! emit_note {
+    ($span:expr, $($tts:tt)*) => {
+        $crate::diagnostic!($span, $crate::Level::Note, $($tts)*).emit()
+    };
+}
+
+/// Shortcut for `emit_note!(Span::call_site(), ...)`.
+///
+/// **Does nothing on stable**
+///
+/// # Syntax
+///
+/// See [the guide](index.html#guide).
+///
+#[macro_export]
+macro_rules! emit_call_site_note {
+    ($($tts:tt)*) => {{
+        $crate::emit_note!($crate::__export::proc_macro2::Span::call_site(), $($tts)*)
+    }};
+}
+
+/// Emit every diagnostic from an iterable while not aborting the proc-macro right away.
+///
+/// The items can be anything that converts into [`Diagnostic`](struct.Diagnostic.html),
+/// like `syn::Error`.
+///
+/// # Syntax
+///
+/// ```ignore
+/// let errors: Vec<Diagnostic> = collect_errors();
+/// emit_errors!(errors);
+/// ```
+#[macro_export]
+macro_rules! emit_errors {
+    ($diagnostics:expr) => {
+        for diag in $diagnostics {
+            $crate::Diagnostic::from(diag).emit();
+        }
+    };
+}
+
+/// Emit every diagnostic from an iterable and abort right after.
+///
+/// See [`emit_errors!`](macro.emit_errors.html).
+#[macro_export]
+macro_rules! abort_all {
+    ($diagnostics:expr) => {{
+        $crate::emit_errors!($diagnostics);
+        $crate::__export::abort_now()
+    }};
+}
+
+// A lone string literal is a format string, like in `format!("{name}")`.
+// Anything else is passed through as is.
+#[cfg(format_lone_literal)]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __pme__format_lone {
+    ($fmt:literal) => {
+        format!($fmt)
+    };
+    ($other:expr) => {
+        $other
+    };
+}
+
+#[cfg(not(format_lone_literal))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __pme__format_lone {
+    ($other:expr) => {
+        $other
+    };
+}
+
 #[doc(hidden)]
 #[macro_export]
 macro_rules! __pme__suggestions {
     ($var:ident) => ();
 
+    // `=??` must go before `=?`, otherwise `? closure` would be parsed as an expression
+
+    ($var:ident $help:ident =?? $msg:expr) => {
+        let $var = $var.lazy_suggestion(stringify!($help), $msg);
+    };
+    ($var:ident $help:ident =?? $span:expr => $msg:expr) => {
+        let $var = $var.lazy_span_suggestion($span.into(), stringify!($help), $msg);
+    };
+
+    ($var:ident $help:ident =?? $msg:expr ; $($rest:tt)*) => {
+        $crate::__pme__suggestions!($var $help =?? $msg);
+        $crate::__pme__suggestions!($var $($rest)*);
+    };
+    ($var:ident $help:ident =?? $span:expr => $msg:expr ; $($rest:tt)*) => {
+        $crate::__pme__suggestions!($var $help =?? $span => $msg);
+        $crate::__pme__suggestions!($var $($rest)*);
+    };
+
     ($var:ident $help:ident =? $msg:expr) => {
         let $var = if let Some(msg) = $msg {
             $var.suggestion(stringify!($help), msg.to_string())
@@ -265,13 +463,13 @@ macro_rules! __pme__suggestions {
         $crate::__pme__suggestions!($var $help = $msg)
     };
     ($var:ident $help:ident = $fmt:expr, $($args:expr),+,) => {
-        $crate::__pme__suggestions!($var $help = $fmt, $($args)*)
+        $crate::__pme__suggestions!($var $help = $fmt, $($args),*)
     };
     ($var:ident $help:ident = $span:expr => $msg:expr,) => {
         $crate::__pme__suggestions!($var $help = $span => $msg)
     };
-    ($var:ident $help:ident = $span:expr => $fmt:expr, $($args:expr),*,) => {
-        $crate::__pme__suggestions!($var $help = $span => $fmt, $($args)*)
+    ($var:ident $help:ident = $span:expr => $fmt:expr, $($args:expr),+,) => {
+        $crate::__pme__suggestions!($var $help = $span => $fmt, $($args),*)
     };
     ($var:ident $help:ident = $msg:expr, ; $($rest:tt)*) => {
         $crate::__pme__suggestions!($var $help = $msg; $($rest)*)
@@ -0,0 +1,125 @@
+//! Facility to test your error reporting code without compiling anything
+//! with `rustc`.
+//!
+//! Available only with the `testing` feature enabled.
+//!
+//! Normally, `proc-macro-error` API can be used only inside functions annotated with
+//! `#[proc_macro_error]`, and the emitted errors end up as `compile_error!`
+//! invocations (or real diagnostics on nightly). This makes testing error paths
+//! possible only via full-blown compilation, with something like `trybuild`.
+//!
+//! [`with_captured_diagnostics`] allows you to run your code in a regular `#[test]`
+//! and inspect the emitted diagnostics instead:
+//!
+//! ```
+//! use proc_macro_error::{emit_call_site_error, testing::with_captured_diagnostics};
+//!
+//! let (res, diagnostics) = with_captured_diagnostics(|| {
+//!     emit_call_site_error!("BOOM");
+//!     42
+//! });
+//!
+//! assert_eq!(res, Some(42));
+//! assert_eq!(diagnostics[0].message(), "BOOM");
+//! ```
+//!
+//! [`with_captured_diagnostics`]: fn.with_captured_diagnostics.html
+
+use std::cell::RefCell;
+use std::panic::{catch_unwind, resume_unwind, UnwindSafe};
+
+use crate::{dummy, imp, AbortNow, Diagnostic, ENTERED_ENTRY_POINT};
+
+thread_local! {
+    static CAPTURED: RefCell<Option<Vec<Diagnostic>>> = RefCell::new(None);
+}
+
+/// Run `f` while collecting all the emitted diagnostics (warnings included)
+/// instead of reporting them to the compiler.
+///
+/// Returns whatever `f` returned (`None` if `f` aborted via `abort!` or alike)
+/// along with the emitted diagnostics in emission order.
+///
+/// The dummy set inside `f` (if any) is discarded.
+pub fn with_captured_diagnostics<F, R>(f: F) -> (Option<R>, Vec<Diagnostic>)
+where
+    F: FnOnce() -> R + UnwindSafe,
+{
+    let old_captured = CAPTURED.with(|captured| captured.replace(Some(Vec::new())));
+    let old_dummy = dummy::replace(None);
+    ENTERED_ENTRY_POINT.with(|flag| flag.set(flag.get() + 1));
+
+    let caught = catch_unwind(f);
+
+    ENTERED_ENTRY_POINT.with(|flag| flag.set(flag.get() - 1));
+    dummy::replace(old_dummy);
+    let diagnostics = CAPTURED
+        .with(|captured| captured.replace(old_captured))
+        .unwrap_or_default();
+
+    match caught {
+        Ok(res) => (Some(res), diagnostics),
+        Err(boxed) => match boxed.downcast::<AbortNow>() {
+            Ok(_) => (None, diagnostics),
+            Err(boxed) => resume_unwind(boxed),
+        },
+    }
+}
+
+/// Reset all the thread-local state of the crate to its initial values:
+/// the stored errors, the dummy, the entry point flag, the settings and
+/// the diagnostics remembered by the `dedup` setting.
+///
+/// Use it to guarantee a clean slate between several macro invocations
+/// performed in the same test. The hook set via [`set_emit_hook`] is left intact.
+///
+/// Must **not** be called inside a live macro, i.e. inside a `#[proc_macro_error]`
+/// function or [`with_captured_diagnostics`]: it would wipe out the state
+/// they rely on.
+///
+/// [`set_emit_hook`]: ../fn.set_emit_hook.html
+/// [`with_captured_diagnostics`]: fn.with_captured_diagnostics.html
+pub fn reset_state() {
+    // settings first: the errors are dropped as is, `summary` and `max_errors`
+    // must not kick in (on nightly they'd be reported right away)
+    crate::reset_entry_point_state();
+    ENTERED_ENTRY_POINT.with(|flag| flag.set(0));
+    dummy::cleanup();
+    drop(imp::save_state());
+}
+
+/// Take the errors stored so far, leaving the storage empty.
+///
+/// This is what `#[proc_macro_error]` does when the macro function returns.
+/// Always empty on nightly, where errors are reported right away.
+pub fn cleanup() -> Vec<Diagnostic> {
+    imp::cleanup()
+}
+
+pub(crate) fn is_capturing() -> bool {
+    CAPTURED.with(|captured| captured.borrow().is_some())
+}
+
+pub(crate) fn capture(diag: Diagnostic) {
+    CAPTURED.with(|captured| {
+        if let Some(captured) = captured.borrow_mut().as_mut() {
+            captured.push(diag);
+        }
+    });
+}
+
+pub(crate) fn error_count() -> usize {
+    CAPTURED.with(|captured| {
+        captured.borrow().as_ref().map_or(0, |captured| {
+            captured.iter().filter(|diag| diag.level.is_error()).count()
+        })
+    })
+}
+
+pub(crate) fn warning_count() -> usize {
+    CAPTURED.with(|captured| {
+        captured.borrow().as_ref().map_or(0, |captured| {
+            captured.iter().filter(|diag| diag.is_warning()).count()
+        })
+    })
+}
@@ -4,8 +4,9 @@ extern crate proc_macro;
 
 use proc_macro2::{Span, TokenStream};
 use proc_macro_error::{
-    abort, abort_call_site, diagnostic, emit_call_site_warning, emit_error, emit_warning,
-    proc_macro_error, set_dummy, Diagnostic, Level, OptionExt, ResultExt, SpanRange,
+    abort, abort_call_site, diagnostic, emit_call_site_note, emit_call_site_warning, emit_error,
+    emit_note, emit_warning, proc_macro_error, set_dummy, Diagnostic, Level, OptionExt,
+    ResultDisplayExt, ResultExt, SpanRange,
 };
 
 use syn::{parse_macro_input, spanned::Spanned};
@@ -71,10 +72,18 @@ pub fn emit(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     .emit();
 
     emit_call_site_error!("emit_call_site_error!(expr) test");
+    emit_call_site_error!("emit_call_site_error!(fmt, {}) test", "args";
+        note = "call site note");
 
     // NOOP on stable, just checking that the macros themselves compile.
     emit_warning!(spans.next().unwrap(), "emit_warning! test");
     emit_call_site_warning!("emit_call_site_warning! test");
+    emit_call_site_warning!("emit_call_site_warning!(fmt, {}) test", "args";
+        note = "call site note");
+    emit_note!(Span::call_site(), "emit_note! test");
+    emit_call_site_note!("emit_call_site_note! test");
+    emit_call_site_note!("emit_call_site_note!(fmt, {}) test", "args";
+        note = "call site note");
 
     quote!().into()
 }
@@ -108,6 +117,11 @@ pub fn abort_notes(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
         note = span2 => "spanned format {}", "note";
         note =? span2 => some_note;
         note =? span2 => none_note;
+
+        help =?? || Some("lazy help");
+        help =?? || none_note;
+        help =?? span2 => || Some(format!("lazy spanned {}", "help"));
+        help =?? span2 => || none_note;
     }
 }
 
@@ -141,6 +155,95 @@ pub fn emit_notes(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     }
 }
 
+#[proc_macro]
+#[proc_macro_error]
+pub fn error_code(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let mut spans = input.into_iter().step_by(2).map(|s| s.span());
+    emit_error! {
+        spans.next().unwrap(), "error with code";
+        code = "E0042";
+        note = "code is not a note"
+    }
+
+    Diagnostic::spanned(
+        spans.next().unwrap().into(),
+        Level::Error,
+        "Diagnostic::code() test".into(),
+    )
+    .code("E0043")
+    .emit();
+
+    quote!().into()
+}
+
+// Iterables
+
+#[proc_macro]
+#[proc_macro_error]
+pub fn emit_errors_test(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let errors = input
+        .into_iter()
+        .step_by(2)
+        .map(|tt| Diagnostic::spanned(tt.span().into(), Level::Error, "emit_errors! test".into()));
+    emit_errors!(errors);
+
+    let syn_errors = vec![syn::Error::new(Span::call_site(), "abort_all! test")];
+    abort_all!(syn_errors)
+}
+
+// Crate path
+
+mod facade {
+    pub(crate) use proc_macro_error as reexport;
+}
+
+#[proc_macro]
+#[proc_macro_error(crate = crate::facade::reexport)]
+pub fn crate_path(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let span = input.into_iter().next().unwrap().span();
+    abort!(span, "crate = path test")
+}
+
+// Deduplication
+
+#[proc_macro]
+#[proc_macro_error(dedup)]
+pub fn dedup(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let spans = input
+        .into_iter()
+        .step_by(2)
+        .map(|s| s.span())
+        .collect::<Vec<_>>();
+    for _ in 0..3 {
+        for span in &spans {
+            emit_error!(span, "dedup test");
+        }
+    }
+    emit_error!(spans[0], "dedup test, different message");
+    for _ in 0..2 {
+        emit_error!(spans[0], "dedup test"; note = "different notes");
+    }
+    quote!().into()
+}
+
+// Error state
+
+#[proc_macro]
+#[proc_macro_error]
+pub fn has_errors(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    assert!(!proc_macro_error::has_errors());
+
+    for tt in input {
+        emit_error!(tt.span(), "has_errors test");
+    }
+
+    if proc_macro_error::has_errors() {
+        emit_call_site_error!("{} errors emitted", proc_macro_error::error_count());
+    }
+
+    quote!().into()
+}
+
 // Extension traits
 
 #[proc_macro]
@@ -151,6 +254,26 @@ pub fn option_ext(_input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     quote!().into()
 }
 
+#[proc_macro]
+#[proc_macro_error]
+pub fn option_unwrap_or_abort(_input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let none: Option<Diagnostic> = None;
+    none.unwrap_or_abort();
+    quote!().into()
+}
+
+#[proc_macro]
+#[proc_macro_error]
+pub fn option_ok_or_abort(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let span = input.into_iter().next().unwrap().span();
+    let none: Option<Diagnostic> = None;
+    none.ok_or_abort(|| {
+        diagnostic!(span, Level::Error, "Option::ok_or_abort() test";
+            help = "lazily built help")
+    });
+    quote!().into()
+}
+
 #[proc_macro]
 #[proc_macro_error]
 pub fn result_unwrap_or_abort(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
@@ -179,6 +302,58 @@ pub fn result_expect_or_abort(input: proc_macro::TokenStream) -> proc_macro::Tok
     quote!().into()
 }
 
+#[proc_macro]
+#[proc_macro_error]
+pub fn result_emit_or(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let values = input
+        .into_iter()
+        .step_by(2)
+        .map(|tt| {
+            let err = Diagnostic::spanned(
+                tt.span().into(),
+                Level::Error,
+                "Result::emit_or() test".to_string(),
+            );
+            let res: Result<u32, _> = Err(err);
+            res.emit_or(0)
+        })
+        .collect::<Vec<_>>();
+
+    emit_call_site_error!("Result::emit_or() yielded {:?}", values);
+    quote!().into()
+}
+
+#[proc_macro]
+#[proc_macro_error]
+pub fn result_map_err_to_diagnostic(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let tt = input.into_iter().next().unwrap();
+    let value = tt
+        .to_string()
+        .parse::<u32>()
+        .map_err_to_diagnostic(tt.span().into())
+        .unwrap_or_abort();
+    quote!(#value).into()
+}
+
+// Conversions
+
+#[proc_macro]
+#[proc_macro_error]
+pub fn into_syn_error(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let mut spans = input.into_iter().step_by(2).map(|s| s.span());
+    let diag = Diagnostic::spanned(
+        spans.next().unwrap().into(),
+        Level::Error,
+        "main message".into(),
+    )
+    .note("folded note".into())
+    .help_indented("indented\nhelp".into())
+    .suggestion("yay", "custom label".into())
+    .span_error(spans.next().unwrap().into(), "child message".into());
+
+    syn::Error::from(diag).to_compile_error().into()
+}
+
 // Dummy
 
 #[proc_macro]
@@ -211,6 +386,33 @@ pub fn append_dummy(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     abort!(span, "append_dummy test")
 }
 
+#[proc_macro]
+#[proc_macro_error]
+pub fn append_dummy_order(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let span = input.into_iter().next().unwrap().span();
+
+    // `const ORDER: [u32; 3] = [1, 2, 3];` is valid only if the pieces are in order
+    set_dummy(quote!(const ORDER));
+    proc_macro_error::append_dummy(quote!(: [u32; 3]));
+    proc_macro_error::append_dummy(quote!(= [1, 2, 3];));
+
+    abort!(span, "append_dummy order test")
+}
+
+#[proc_macro]
+#[proc_macro_error]
+pub fn abort_with_dummy_test(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let span = input.into_iter().next().unwrap().span();
+    let dummy = quote! {
+        impl Default for NeedDefault {
+            fn default() -> Self { NeedDefault::A }
+        }
+    };
+
+    abort_with_dummy!(dummy; span, "abort_with_dummy! test";
+        note = "with a note")
+}
+
 // Panic
 
 #[proc_macro]
@@ -255,6 +457,10 @@ pub fn explicit_span_range(input: proc_macro::TokenStream) -> proc_macro::TokenS
     let mut spans = input.into_iter().step_by(2).map(|s| s.span());
     let first = Span::from(spans.next().unwrap());
     let last = Span::from(spans.nth(1).unwrap());
+    emit_error!(
+        SpanRange::from((first, last)),
+        "SpanRange from (Span, Span)"
+    );
     abort!(SpanRange { first, last }, "explicit SpanRange")
 }
 
@@ -273,3 +479,400 @@ pub fn children_messages(input: proc_macro::TokenStream) -> proc_macro::TokenStr
     main.combine(child);
     Diagnostic::from(main).abort()
 }
+
+// Escaping
+
+#[proc_macro]
+#[proc_macro_error]
+pub fn escaping(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let span = input.into_iter().next().unwrap().span();
+    abort!(span, "line1\nline2\t\"quoted\" \\backslash\\")
+}
+
+// Notes formatting
+
+#[proc_macro]
+#[proc_macro_error]
+pub fn two_notes(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let span = input.into_iter().next().unwrap().span();
+    abort!(span, "main message";
+        note = "first note";
+        help = "second note")
+}
+
+// Indented notes
+
+#[proc_macro]
+#[proc_macro_error]
+pub fn indented_notes(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let span = input.into_iter().next().unwrap().span();
+    Diagnostic::spanned(span.into(), Level::Error, "main message".into())
+        .note_indented("first line\nsecond line".into())
+        .abort()
+}
+
+// Custom level
+
+#[proc_macro]
+#[proc_macro_error]
+pub fn custom_level(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let span = input.into_iter().next().unwrap().span();
+    diagnostic!(span, Level::Custom("lint".into()), "custom level test")
+        .code("L001")
+        .note("custom level note".into())
+        .emit();
+    quote!().into()
+}
+
+// Source context
+
+#[proc_macro]
+#[proc_macro_error]
+pub fn source_context(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let span = input.into_iter().next().unwrap().span();
+    Diagnostic::spanned(span.into(), Level::Error, "source context test".into())
+        .with_source_context()
+        .abort()
+}
+
+// Warnings as errors
+
+#[proc_macro]
+#[proc_macro_error(warn_as_error)]
+pub fn warn_as_error(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let mut spans = input.into_iter().step_by(2).map(|s| s.span());
+    emit_warning!(spans.next().unwrap(), "warning turned into error");
+    Diagnostic::spanned(spans.next().unwrap().into(), Level::Error, "error".into())
+        .span_warning(spans.next().unwrap().into(), "child warning".into())
+        .emit();
+    quote!().into()
+}
+
+// Expression position
+
+#[proc_macro]
+#[proc_macro_error(expr_context)]
+pub fn expr_context(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let mut spans = input.into_iter().step_by(2).map(|s| s.span());
+    set_dummy(quote!(0u32));
+    emit_error!(spans.next().unwrap(), "first error");
+    emit_error!(spans.next().unwrap(), "second error");
+    quote!(0u32).into()
+}
+
+// Children with notes
+
+#[proc_macro]
+#[proc_macro_error]
+pub fn child_notes(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let mut spans = input.into_iter().step_by(2).map(|s| s.span());
+    let first = spans.next().unwrap();
+    let second = spans.next().unwrap();
+    Diagnostic::spanned(first.into(), Level::Error, "conflicting attributes".into())
+        .child(
+            Diagnostic::spanned(second.into(), Level::Error, "also here".into())
+                .help("remove one of them".into()),
+        )
+        .abort()
+}
+
+// Manual setup
+
+#[proc_macro]
+pub fn catch(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    proc_macro_error::catch(|| {
+        let span = input.into_iter().next().unwrap().span();
+        abort!(span, "aborted inside catch")
+    })
+}
+
+// Warning count
+
+#[proc_macro]
+#[proc_macro_error]
+pub fn warning_count(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    for tt in input {
+        emit_warning!(tt.span(), "warning_count test");
+    }
+
+    emit_call_site_error!("warning count: {}", proc_macro_error::warning_count());
+    quote!().into()
+}
+
+// Spanned notes as errors
+
+#[proc_macro]
+#[proc_macro_error]
+pub fn spanned_notes_as_errors(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let mut spans = input.into_iter().step_by(2).map(|s| s.span());
+    let first = spans.next().unwrap();
+    let second = spans.next().unwrap();
+    Diagnostic::spanned(first.into(), Level::Error, "main message".into())
+        .note("inline note".into())
+        .span_help(second.into(), "spanned help".into())
+        .spanned_notes_as_errors()
+        .abort()
+}
+
+// Template diagnostic
+
+#[proc_macro]
+#[proc_macro_error]
+pub fn template_diagnostic(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let template = Diagnostic::new(Level::Error, "forbidden token".into()).help("remove it".into());
+    for tt in input {
+        template
+            .clone()
+            .with_spans(Span::from(tt.span()).into())
+            .emit();
+    }
+    quote!().into()
+}
+
+// proc_macro::Span
+
+#[proc_macro]
+#[proc_macro_error]
+pub fn proc_macro_span(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let span = input.into_iter().next().unwrap().span();
+    Diagnostic::spanned_range(span, Level::Error, "proc_macro::Span as is".into()).abort()
+}
+
+// Errors limit
+
+#[proc_macro]
+#[proc_macro_error(max_errors = 2)]
+pub fn max_errors(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    for tt in input {
+        emit_error!(tt.span(), "max_errors test");
+    }
+    quote!().into()
+}
+
+// Manual output layout
+
+#[proc_macro]
+#[proc_macro_error]
+pub fn drain_errors(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    for tt in input {
+        emit_error!(tt.span(), "drained error");
+    }
+    let errors = proc_macro_error::drain_errors();
+    quote!( #(#errors)* fn generated() {} ).into()
+}
+
+// Generic helper with a where clause
+
+#[proc_macro_error(allow_not_macro, assert_unwind_safe)]
+fn abort_on_first<T>(tokens: T) -> proc_macro::TokenStream
+where
+    T: IntoIterator<Item = proc_macro::TokenTree>,
+{
+    let span = tokens.into_iter().next().unwrap().span();
+    abort!(span, "aborted from a generic helper")
+}
+
+#[proc_macro]
+pub fn generic_helper(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    abort_on_first(input)
+}
+
+// Attributes after `#[proc_macro_error]`
+
+#[proc_macro]
+#[proc_macro_error]
+/// Expands to a function named after the input.
+#[allow(unused_variables)]
+#[inline]
+pub fn many_attrs(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let unused = ();
+    let input = TokenStream::from(input);
+    quote!(fn #input() {}).into()
+}
+
+// Independent dummy contributors
+
+fn dummy_default_impl() {
+    proc_macro_error::add_dummy(quote! {
+        impl Default for NeedDefault {
+            fn default() -> Self { NeedDefault::A }
+        }
+    });
+}
+
+fn dummy_clone_impl() {
+    proc_macro_error::add_dummy(quote! {
+        impl Clone for NeedDefault {
+            fn clone(&self) -> Self { NeedDefault::A }
+        }
+    });
+}
+
+#[proc_macro]
+#[proc_macro_error]
+pub fn dummy_contributors(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let span = input.into_iter().next().unwrap().span();
+    dummy_default_impl();
+    dummy_clone_impl();
+    abort!(span, "dummy contributors test")
+}
+
+// Two-span conflict
+
+#[proc_macro]
+#[proc_macro_error]
+pub fn span_conflict(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let mut spans = input.into_iter().step_by(2).map(|s| s.span());
+    let first = spans.next().unwrap();
+    let second = spans.next().unwrap();
+    Diagnostic::spanned(second.into(), Level::Error, "duplicate definition".into())
+        .span_conflict(first.into(), "first defined here".into())
+        .abort()
+}
+
+// Helper returning `proc_macro2::TokenStream`
+
+#[proc_macro_error(allow_not_macro, assert_unwind_safe)]
+fn gen_struct(input: TokenStream) -> TokenStream {
+    let mut tokens = input.into_iter();
+    let name = tokens.next().unwrap();
+    if let Some(extra) = tokens.next() {
+        emit_error!(extra, "unexpected token");
+    }
+    quote!(struct #name;)
+}
+
+#[proc_macro]
+pub fn proc_macro2_helper(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    gen_struct(input.into()).into()
+}
+
+// Errors emitted before an unrelated panic
+
+#[proc_macro]
+#[proc_macro_error]
+pub fn emit_then_panic(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let span = input.into_iter().next().unwrap().span();
+    emit_error!(span, "emitted before the panic");
+    panic!("unrelated panic test")
+}
+
+// No specific location
+
+#[proc_macro]
+#[proc_macro_error]
+pub fn unspanned(_input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    Diagnostic::unspanned(Level::Error, "requires the `foo` feature".into()).abort()
+}
+
+// Single line messages
+
+#[proc_macro]
+#[proc_macro_error(single_line)]
+pub fn single_line(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let span = input.into_iter().next().unwrap().span();
+    abort!(span, "main message\nsecond line";
+        note = "some note";
+        help = "some help")
+}
+
+// syn::Error with an explicit range
+
+#[proc_macro]
+#[proc_macro_error]
+pub fn from_syn_spanned(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = TokenStream::from(input);
+    let first = input.clone().into_iter().next().unwrap();
+    let err = syn::Error::new(first.span(), "the whole node is wrong");
+    Diagnostic::from_syn_spanned(&err, SpanRange::from_tokens(&input)).abort()
+}
+
+// Errors count summary
+
+#[proc_macro]
+#[proc_macro_error(summary)]
+pub fn summary(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    for tt in input {
+        emit_error!(tt.span(), "summary test");
+    }
+    quote!().into()
+}
+
+// Diagnostic underlining a whole `syn` node
+
+#[proc_macro]
+#[proc_macro_error]
+pub fn spans_from(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let ty = parse_macro_input!(input as syn::Type);
+    Diagnostic::new(Level::Error, "unsupported type".into())
+        .with_spans_from(&ty)
+        .abort()
+}
+
+// Panic turned into an error, the errors emitted before it are kept
+
+#[proc_macro]
+#[proc_macro_error(emit_on_panic)]
+pub fn emit_on_panic(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let span = input.into_iter().next().unwrap().span();
+    emit_error!(span, "emitted before the panic");
+    None::<()>.expect("emit_on_panic test");
+    quote!().into()
+}
+
+// Wrapped helper called after the outer macro emitted an error
+
+#[proc_macro]
+#[proc_macro_error]
+pub fn nested_entry_point(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let mut tokens = TokenStream::from(input).into_iter();
+    let first = tokens.next().unwrap();
+    emit_error!(first, "outer error");
+
+    let helper = gen_struct(tokens.collect());
+    emit_error!(
+        first,
+        "outer error count after the helper: {}",
+        proc_macro_error::error_count()
+    );
+    set_dummy(helper);
+    quote!().into()
+}
+
+// Errors emitted before a panic with a formatted message or no message at all
+
+#[proc_macro]
+#[proc_macro_error]
+pub fn emit_then_panic_string(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let span = input.clone().into_iter().next().unwrap().span();
+    emit_error!(span, "emitted before the panic");
+    panic!("unrelated panic test: {}", input)
+}
+
+#[proc_macro]
+#[proc_macro_error]
+pub fn emit_then_panic_payload(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let span = input.into_iter().next().unwrap().span();
+    emit_error!(span, "emitted before the panic");
+    std::panic::resume_unwind(Box::new(42))
+}
+
+// Fallback spans inside a running macro
+
+#[proc_macro]
+#[proc_macro_error]
+pub fn fallback_span(_input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    // every `proc_macro2` span created from now on is a fallback one,
+    // the output is converted to the compiler's tokens via a string
+    proc_macro2::fallback::force();
+    emit_error!(Span::call_site(), "fallback span");
+    quote!().into()
+}
+
+// Missing attribute
+
+#[proc_macro]
+pub fn missing_attr(_input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    emit_call_site_error!("You won't see me");
+    quote!().into()
+}
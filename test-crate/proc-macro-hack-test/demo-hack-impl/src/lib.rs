@@ -1,7 +1,7 @@
 extern crate proc_macro;
 
 use proc_macro::TokenStream;
-use proc_macro_error::{emit_error, proc_macro_error};
+use proc_macro_error::{emit_error, proc_macro_error, set_dummy};
 use proc_macro_hack::proc_macro_hack;
 use quote::quote;
 use syn::{parse_macro_input, Expr};
@@ -16,3 +16,27 @@ pub fn add_one(input: TokenStream) -> TokenStream {
         1 + (#expr)
     })
 }
+
+#[proc_macro_error]
+#[proc_macro_hack]
+pub fn add_one_dummy(input: TokenStream) -> TokenStream {
+    let expr = parse_macro_input!(input as Expr);
+    // the dummy relies on the `proc_macro_call!` helper generated for the hack
+    set_dummy(quote!(proc_macro_call!();));
+    emit_error!(expr, "BOOM");
+
+    TokenStream::from(quote! {
+        1 + (#expr)
+    })
+}
+
+#[proc_macro_error]
+#[proc_macro_hack::proc_macro_hack]
+pub fn add_one_qualified(input: TokenStream) -> TokenStream {
+    let expr = parse_macro_input!(input as Expr);
+    emit_error!(expr, "BOOM");
+
+    TokenStream::from(quote! {
+        1 + (#expr)
+    })
+}
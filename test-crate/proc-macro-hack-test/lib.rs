@@ -5,3 +5,11 @@ use proc_macro_hack::proc_macro_hack;
 /// (Documentation goes here on the re-export, not in the other crate.)
 #[proc_macro_hack]
 pub use demo_hack_impl::add_one;
+
+/// Same as `add_one!` but with a dummy.
+#[proc_macro_hack]
+pub use demo_hack_impl::add_one_dummy;
+
+/// Same as `add_one!`, the hack attribute is applied by its full path.
+#[proc_macro_hack]
+pub use demo_hack_impl::add_one_qualified;
@@ -0,0 +1,21 @@
+#![cfg(all(feature = "debug-backtrace", feature = "testing"))]
+
+use proc_macro_error::{emit_call_site_error, testing::with_captured_diagnostics};
+
+#[test]
+fn backtrace_note() {
+    std::env::set_var("PME_BACKTRACE", "1");
+    let (_, diagnostics) = with_captured_diagnostics(|| {
+        emit_call_site_error!("error");
+    });
+    std::env::remove_var("PME_BACKTRACE");
+
+    let rendered = diagnostics[0].render();
+    assert!(rendered.starts_with("error: error\n  = note: backtrace:\n"));
+    assert!(rendered.contains("backtrace_note"));
+
+    let (_, diagnostics) = with_captured_diagnostics(|| {
+        emit_call_site_error!("error");
+    });
+    assert_eq!(diagnostics[0].render(), "error: error\n");
+}
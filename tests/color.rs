@@ -0,0 +1,26 @@
+#![cfg(feature = "color")]
+
+use proc_macro2::Span;
+use proc_macro_error::{Diagnostic, Level};
+
+#[test]
+fn render_colored() {
+    let diag = Diagnostic::new(Level::Error, "main message".into())
+        .code("E0042")
+        .note("some note".into())
+        .span_warning(Span::call_site(), "child warning".into());
+
+    assert_eq!(
+        diag.render_colored(),
+        "\x1b[1;31merror[E0042]\x1b[0m: main message\n  \
+         = note: some note\n\
+         \n\
+         \x1b[1;33mwarning\x1b[0m: child warning\n"
+    );
+}
+
+#[test]
+fn level_color() {
+    assert_eq!(Level::Custom("lint".into()).color(), "\x1b[1;31m");
+    assert_eq!(Level::NonExhaustive.color(), "");
+}
@@ -0,0 +1,42 @@
+#![cfg(feature = "json")]
+
+use proc_macro2::{Span, TokenStream};
+use proc_macro_error::{Diagnostic, Level};
+
+#[test]
+fn to_json() {
+    let diag = Diagnostic::new(Level::Error, "main \"message\"\n".into())
+        .code("E0042")
+        .note("some note".into());
+
+    assert_eq!(
+        diag.to_json(),
+        r#"{"message":"main \"message\"\n","code":{"code":"E0042","explanation":null},"level":"error","label":null,"spans":[{"line_start":1,"column_start":1,"line_end":1,"column_end":1,"is_primary":true,"suggested_replacement":null}],"children":[{"message":"some note","code":null,"level":"note","label":null,"spans":[],"children":[]}]}"#
+    );
+}
+
+#[test]
+fn to_json_spans() {
+    let tokens = "first\n  second".parse::<TokenStream>().unwrap();
+    let spans = tokens.into_iter().map(|tt| tt.span()).collect::<Vec<_>>();
+
+    let diag = Diagnostic::spanned(spans[0], Level::Warning, "warning".into())
+        .span_suggestion_replacement(spans[1], "try".into(), "third".into())
+        .span_error(Span::call_site(), "child".into());
+
+    assert_eq!(
+        diag.to_json(),
+        r#"{"message":"warning","code":null,"level":"warning","label":null,"spans":[{"line_start":1,"column_start":1,"line_end":1,"column_end":6,"is_primary":true,"suggested_replacement":null}],"children":[{"message":"try","code":null,"level":"help","label":null,"spans":[{"line_start":2,"column_start":3,"line_end":2,"column_end":9,"is_primary":true,"suggested_replacement":"third"}],"children":[]},{"message":"child","code":null,"level":"error","label":null,"spans":[{"line_start":1,"column_start":1,"line_end":1,"column_end":1,"is_primary":true,"suggested_replacement":null}],"children":[]}]}"#
+    );
+}
+
+#[test]
+fn to_json_custom_labels() {
+    let diag = Diagnostic::new(Level::Custom("lint".into()), "custom".into())
+        .suggestion("yay", "custom note".into());
+
+    assert_eq!(
+        diag.to_json(),
+        r#"{"message":"custom","code":null,"level":"error","label":"lint","spans":[{"line_start":1,"column_start":1,"line_end":1,"column_end":1,"is_primary":true,"suggested_replacement":null}],"children":[{"message":"custom note","code":null,"level":"note","label":"yay","spans":[],"children":[]}]}"#
+    );
+}
@@ -0,0 +1,61 @@
+#![cfg(feature = "miette")]
+
+use miette_crate::{MietteDiagnostic, Report, Severity};
+use proc_macro2::Span;
+use proc_macro_error::{Diagnostic, Level};
+
+#[test]
+fn to_miette() {
+    let diag = Diagnostic::new(Level::Error, "main message".into())
+        .code("E0042")
+        .note("some note".into())
+        .help("some help".into())
+        .span_error(Span::call_site(), "child message".into());
+
+    let miette = MietteDiagnostic::from(diag);
+    assert_eq!(miette.message, "main message");
+    assert_eq!(miette.code, Some("E0042".to_string()));
+    assert_eq!(miette.severity, Some(Severity::Error));
+    assert_eq!(
+        miette.help,
+        Some("note: some note\nhelp: some help\nerror: child message".to_string())
+    );
+    assert!(miette.labels.is_none());
+
+    let warning = Diagnostic::new(Level::Warning, "warning".into());
+    let report = Report::from(warning);
+    assert_eq!(report.severity(), Some(Severity::Warning));
+    assert_eq!(report.to_string(), "warning");
+}
+
+#[test]
+fn to_miette_labels() {
+    use proc_macro2::TokenStream;
+    use proc_macro_error::SpanRange;
+
+    let tokens = "first (second) third".parse::<TokenStream>().unwrap();
+    let spans = tokens.into_iter().map(|tt| tt.span()).collect::<Vec<_>>();
+
+    let diag = Diagnostic::spanned_range(
+        SpanRange {
+            first: spans[0],
+            last: spans[1],
+        },
+        Level::Error,
+        "main message".into(),
+    )
+    .span_error(spans[2], "child message".into());
+
+    let labels = MietteDiagnostic::from(diag).labels.unwrap();
+    let labels = labels
+        .iter()
+        .map(|l| (l.label(), l.offset(), l.len(), l.primary()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        labels,
+        [
+            (None, 0, 14, true),
+            (Some("error: child message"), 15, 5, false)
+        ]
+    );
+}
@@ -8,3 +8,10 @@ ok!(it_works);
 fn check_it_works() {
     it_works();
 }
+
+many_attrs!(many_attrs_work);
+
+#[test]
+fn check_many_attrs() {
+    many_attrs_work();
+}
@@ -0,0 +1,377 @@
+use proc_macro2::Span;
+use proc_macro_error::{diagnostic, set_note_prefix, warning, Diagnostic, Level, SpanRange};
+
+#[test]
+fn render() {
+    let diag = Diagnostic::new(Level::Error, "main message".into())
+        .note("some note".into())
+        .help("multiline\nhelp\n".into())
+        .span_suggestion_replacement(Span::call_site(), "try".into(), "foo()".into())
+        .span_error(Span::call_site(), "child message".into())
+        .span_warning(Span::call_site(), "child warning".into());
+
+    assert_eq!(
+        diag.render(),
+        "error: main message\n  \
+         = note: some note\n  \
+         = help: multiline\nhelp\n  \
+         = help: try: `foo()`\n\
+         \n\
+         error: child message\n\
+         \n\
+         warning: child warning\n"
+    );
+
+    let children = diag.children().map(|(_, _, msg)| msg).collect::<Vec<_>>();
+    assert_eq!(children, ["child message", "child warning"]);
+
+    let diag = Diagnostic::new(Level::Warning, "warning".into());
+    assert_eq!(diag.render(), "warning: warning\n");
+
+    let diag = Diagnostic::new(Level::Error, "coded".into()).code("E0042");
+    assert_eq!(diag.render(), "error[E0042]: coded\n");
+}
+
+#[test]
+fn to_tokens_escapes_message() {
+    use quote::ToTokens;
+
+    let diag = Diagnostic::new(Level::Error, "crlf\r\nnul \0 \"quoted\" \\".into());
+    let tokens = diag.into_token_stream().to_string();
+    assert!(
+        tokens.contains(r#""crlf\r\nnul \0 \"quoted\" \\""#),
+        "{}",
+        tokens
+    );
+}
+
+#[test]
+fn warning_with_children_to_tokens() {
+    use quote::ToTokens;
+
+    let diag = Diagnostic::new(Level::Warning, "warning".into())
+        .span_error(Span::call_site(), "child error".into());
+    assert!(diag.into_token_stream().is_empty());
+}
+
+#[test]
+fn conditional_notes() {
+    let diag = Diagnostic::new(Level::Error, "main message".into())
+        .note_if(true, "shown note".into())
+        .note_if(false, "hidden note".into())
+        .help_if(false, "hidden help".into())
+        .help_if(true, "shown help".into());
+
+    assert_eq!(
+        diag.render(),
+        "error: main message\n  \
+         = note: shown note\n  \
+         = help: shown help\n"
+    );
+}
+
+#[test]
+fn custom_level() {
+    let diag = Diagnostic::new(Level::Custom("lint".into()), "main message".into()).code("L001");
+    assert_eq!(diag.render(), "lint[L001]: main message\n");
+}
+
+#[test]
+fn from_str() {
+    assert_eq!(Diagnostic::from("message").render(), "error: message\n");
+
+    let diag = Diagnostic::from((Span::call_site(), "spanned message"));
+    assert_eq!(diag.render(), "error: spanned message\n");
+}
+
+#[test]
+fn change_level() {
+    let mut diag = Diagnostic::new(Level::Error, "message".into()).with_level(Level::Warning);
+    assert_eq!(diag.render(), "warning: message\n");
+
+    diag.set_level(Level::Error);
+    assert_eq!(diag.render(), "error: message\n");
+}
+
+#[test]
+fn warning_builder() {
+    let diag =
+        warning!(Span::call_site(), "deprecated {}", "attribute"; note = "use `bar` instead");
+    assert_eq!(
+        diag.render(),
+        "warning: deprecated attribute\n  = note: use `bar` instead\n"
+    );
+}
+
+#[test]
+fn prepend_append_message() {
+    let mut diag = Diagnostic::new(Level::Error, "unexpected token".into());
+    diag.prepend_message("in attribute `foo`: ");
+    diag.append_message(", expected `=`");
+    assert_eq!(
+        diag.render(),
+        "error: in attribute `foo`: unexpected token, expected `=`\n"
+    );
+}
+
+#[test]
+fn map_message() {
+    let diags = vec![
+        Diagnostic::new(Level::Error, "unknown field".into()),
+        Diagnostic::new(Level::Warning, "deprecated field".into()).note("use `bar`".into()),
+    ];
+
+    let rendered = diags
+        .into_iter()
+        .map(|diag| diag.map_message(|msg| format!("[attrs] {}", msg)).render())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        rendered,
+        [
+            "error: [attrs] unknown field\n",
+            "warning: [attrs] deprecated field\n  = note: use `bar`\n",
+        ]
+    );
+}
+
+#[test]
+fn children_with_notes() {
+    let diag = Diagnostic::new(Level::Error, "conflicting attributes".into())
+        .child(
+            Diagnostic::new(Level::Error, "first one here".into())
+                .help("remove one of them".into())
+                .span_warning(Span::call_site(), "nested child".into()),
+        )
+        .span_error(Span::call_site(), "second one here".into());
+
+    assert_eq!(
+        diag.render(),
+        "error: conflicting attributes\n\
+         \n\
+         error: first one here\n  \
+         = help: remove one of them\n\
+         \n\
+         warning: nested child\n\
+         \n\
+         error: second one here\n"
+    );
+
+    let children = diag.children().map(|(_, _, msg)| msg).collect::<Vec<_>>();
+    assert_eq!(children, ["first one here", "second one here"]);
+}
+
+#[test]
+fn level_to_from_str() {
+    assert_eq!(Level::Error.to_string(), "error");
+    assert_eq!(Level::Warning.to_string(), "warning");
+    assert_eq!(Level::Note.to_string(), "note");
+    assert_eq!(Level::Custom("lint".into()).to_string(), "lint");
+    assert_eq!(Level::NonExhaustive.to_string(), "nonexhaustive");
+
+    assert_eq!("error".parse(), Ok(Level::Error));
+    assert_eq!("warning".parse(), Ok(Level::Warning));
+    assert_eq!("note".parse(), Ok(Level::Note));
+
+    let err = "fatal".parse::<Level>().unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "unknown diagnostic level `fatal`, expected `error`, `warning` or `note`"
+    );
+}
+
+#[test]
+fn indented_notes() {
+    let diag = Diagnostic::new(Level::Error, "main message".into())
+        .note_indented("first line\nsecond line\n".into())
+        .help_indented("first line\nsecond line".into());
+
+    assert_eq!(
+        diag.render(),
+        "error: main message\n  \
+         = note: first line\n          second line\n  \
+         = help: first line\n          second line\n"
+    );
+}
+
+#[cfg(feature = "syn-error")]
+#[test]
+fn from_unusual_syn_error() {
+    use proc_macro2::TokenStream;
+
+    let empty = syn::Error::new_spanned(TokenStream::new(), "empty tokens");
+    assert_eq!(Diagnostic::from(empty).render(), "error: empty tokens\n");
+
+    let group = "({ [] })".parse::<TokenStream>().unwrap();
+    let mut combined = syn::Error::new_spanned(group, "nested groups");
+    combined.combine(syn::Error::new_spanned(TokenStream::new(), "child"));
+    assert_eq!(
+        Diagnostic::from(combined).render(),
+        "error: nested groups\n\nerror: child\n"
+    );
+}
+
+#[cfg(feature = "syn-error")]
+#[test]
+fn from_syn_errors() {
+    let errors = vec![
+        syn::Error::new(Span::call_site(), "first"),
+        syn::Error::new(Span::call_site(), "second"),
+        syn::Error::new(Span::call_site(), "third"),
+    ];
+
+    assert_eq!(
+        Diagnostic::from_syn_errors(errors).unwrap().render(),
+        "error: first\n\nerror: second\n\nerror: third\n"
+    );
+}
+
+#[cfg(feature = "syn-error")]
+#[test]
+fn from_syn_errors_empty() {
+    assert!(Diagnostic::from_syn_errors(Vec::new()).is_none());
+}
+
+#[test]
+fn dedup_children() {
+    let mut diag = Diagnostic::new(Level::Error, "main message".into())
+        .span_error(Span::call_site(), "missing field `a`".into())
+        .span_error(Span::call_site(), "missing field `b`".into())
+        .span_error(Span::call_site(), "missing field `a`".into());
+
+    diag.dedup_children();
+    assert_eq!(diag.children().count(), 2);
+}
+
+#[test]
+fn dedup_children_with_different_notes() {
+    let mut diag = Diagnostic::new(Level::Error, "main message".into()).with_related(vec![
+        Diagnostic::new(Level::Error, "missing field".into()).note("field `a`".into()),
+        Diagnostic::new(Level::Error, "missing field".into()).note("field `b`".into()),
+        Diagnostic::new(Level::Error, "missing field".into()).note("field `a`".into()),
+    ]);
+
+    diag.dedup_children();
+    assert_eq!(diag.children().count(), 2);
+}
+
+#[cfg(feature = "syn-error")]
+#[test]
+fn from_syn_errors_dedup() {
+    let errors = vec![
+        syn::Error::new(Span::call_site(), "first"),
+        syn::Error::new(Span::call_site(), "missing field `a`"),
+        syn::Error::new(Span::call_site(), "missing field `a`"),
+    ];
+
+    assert_eq!(
+        Diagnostic::from_syn_errors(errors)
+            .unwrap()
+            .children()
+            .count(),
+        1
+    );
+}
+
+#[test]
+fn default_span_range() {
+    let diag = Diagnostic::spanned_range(SpanRange::default(), Level::Error, "fallback".into());
+    assert_eq!(diag.render(), "error: fallback\n");
+}
+
+#[test]
+fn diagnostic_from_with_notes() {
+    let diag = diagnostic!(from "converted";
+        help = "try {}", "this";
+        note = "a note"
+    );
+
+    assert_eq!(
+        diag.render(),
+        "error: converted\n  = help: try this\n  = note: a note\n"
+    );
+}
+
+#[test]
+fn note_prefix() {
+    let diag = Diagnostic::new(Level::Error, "main message".into())
+        .note("some note".into())
+        .help("some help".into());
+
+    set_note_prefix("> ");
+    let rendered = diag.render();
+    set_note_prefix("  = ");
+
+    assert_eq!(
+        rendered,
+        "error: main message\n> note: some note\n> help: some help\n"
+    );
+}
+
+#[test]
+fn level_predicates() {
+    let diags = [
+        Diagnostic::new(Level::Error, "error".into()),
+        Diagnostic::new(Level::Warning, "warning".into()),
+        Diagnostic::new(Level::Custom("lint".into()), "custom".into()),
+    ];
+
+    let errors = diags
+        .iter()
+        .filter(|d| d.is_error())
+        .map(Diagnostic::message)
+        .collect::<Vec<_>>();
+    assert_eq!(errors, ["error", "custom"]);
+
+    let warnings = diags.iter().filter(|d| d.is_warning()).count();
+    assert_eq!(warnings, 1);
+    assert_eq!(*diags[1].level(), Level::Warning);
+}
+
+#[test]
+fn with_related() {
+    let diag = Diagnostic::new(Level::Error, "malformed attribute".into()).with_related(vec![
+        Diagnostic::new(Level::Error, "unknown field `a`".into()),
+        Diagnostic::new(Level::Error, "unknown field `b`".into()).help("check the spelling".into()),
+    ]);
+
+    assert_eq!(
+        diag.render(),
+        "error: malformed attribute\n\
+         \n\
+         error: unknown field `a`\n  \
+         = note: caused by: malformed attribute\n\
+         \n\
+         error: unknown field `b`\n  \
+         = help: check the spelling\n  \
+         = note: caused by: malformed attribute\n"
+    );
+}
+
+#[test]
+fn trailing_separators_in_notes() {
+    let expected = "error: main message\n  = note: first note\n  = help: second a b\n";
+
+    let diag = diagnostic!(Span::call_site(), Level::Error, "main message";
+        note = "first note";
+        help = "second {} {}", "a", "b";
+    );
+    assert_eq!(diag.render(), expected);
+
+    let diag = diagnostic!(Span::call_site(), Level::Error, "main message";
+        note = "first note",;
+        help = "second {} {}", "a", "b",
+    );
+    assert_eq!(diag.render(), expected);
+
+    let diag = diagnostic!(Span::call_site(), Level::Error, "main message";
+        note = Span::call_site() => "first note";
+        help = Span::call_site() => "second {} {}", "a", "b",
+    );
+    assert_eq!(diag.render(), expected);
+
+    let diag = diagnostic!(Span::call_site(), Level::Error, "main message";
+        note =? Some("first note");
+        help =?? || Some("second a b");
+    );
+    assert_eq!(diag.render(), expected);
+}
@@ -11,3 +11,52 @@ fn missing_attr_emit() {
 fn missing_attr_abort() {
     abort_call_site!("You won't see me");
 }
+
+// Outside of a proc-macro all the spans are `proc_macro2` fallback spans.
+// The nightly backend can't report them, but must not panic either.
+#[test]
+fn fallback_spans() {
+    let span = proc_macro2::Span::call_site();
+    let _: proc_macro2::TokenStream = entry_point(
+        || {
+            Diagnostic::spanned(span, Level::Error, "fallback span".into())
+                .span_note(span, "fallback note".into())
+                .emit();
+            assert_eq!(error_count(), 1);
+            proc_macro2::TokenStream::new()
+        },
+        EntryPointSettings::default(),
+    );
+}
+
+#[test]
+fn max_errors_outside_of_macro() {
+    let settings = EntryPointSettings {
+        max_errors: Some(1),
+        ..EntryPointSettings::default()
+    };
+    let _: proc_macro2::TokenStream = entry_point(
+        || {
+            emit_call_site_error!("first");
+            emit_call_site_error!("second");
+            proc_macro2::TokenStream::new()
+        },
+        settings,
+    );
+}
+
+#[test]
+fn summary_outside_of_macro() {
+    let settings = EntryPointSettings {
+        summary: true,
+        ..EntryPointSettings::default()
+    };
+    let _: proc_macro2::TokenStream = entry_point(
+        || {
+            emit_call_site_error!("first");
+            emit_call_site_error!("second");
+            proc_macro2::TokenStream::new()
+        },
+        settings,
+    );
+}
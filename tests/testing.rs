@@ -0,0 +1,320 @@
+#![cfg(feature = "testing")]
+
+use proc_macro2::{Span, TokenStream};
+use proc_macro_error::{
+    abort, abort_call_site, emit_call_site_error, emit_call_site_note, emit_call_site_warning,
+    emit_error, entry_point, has_errors, set_dummy,
+    testing::{cleanup, reset_state, with_captured_diagnostics},
+    warning, warning_count, Diagnostic, EntryPointSettings, Level, ResultVecExt,
+};
+use quote::quote;
+
+#[test]
+fn captures_emitted() {
+    let (res, diagnostics) = with_captured_diagnostics(|| {
+        emit_call_site_warning!("warning");
+        assert!(!has_errors());
+        assert_eq!(warning_count(), 1);
+
+        emit_call_site_error!("error");
+        assert!(has_errors());
+        assert_eq!(warning_count(), 1);
+        "done"
+    });
+
+    assert_eq!(res, Some("done"));
+    let messages = diagnostics.iter().map(|d| d.message()).collect::<Vec<_>>();
+    assert_eq!(messages, ["warning", "error"]);
+}
+
+#[test]
+fn call_site_levels() {
+    let (_, diagnostics) = with_captured_diagnostics(|| {
+        emit_call_site_error!("error");
+        emit_call_site_warning!("warning");
+        emit_call_site_note!("note {}", 1; help = "help");
+        assert_eq!(warning_count(), 1);
+    });
+
+    let levels = diagnostics.iter().map(|d| d.level()).collect::<Vec<_>>();
+    assert_eq!(levels, [&Level::Error, &Level::Warning, &Level::Note]);
+    assert_eq!(diagnostics[2].render(), "note: note 1\n  = help: help\n");
+}
+
+#[test]
+fn captures_aborted() {
+    let (res, diagnostics) = with_captured_diagnostics(|| {
+        set_dummy(quote!(dummy));
+        emit_call_site_error!("first");
+        abort_call_site!("second")
+    });
+
+    assert!(res.is_none());
+    let messages = diagnostics.iter().map(|d| d.message()).collect::<Vec<_>>();
+    assert_eq!(messages, ["first", "second"]);
+}
+
+#[test]
+#[should_panic = "unrelated panic"]
+fn propagates_panics() {
+    with_captured_diagnostics(|| panic!("unrelated panic"));
+}
+
+#[test]
+fn emit_hook() {
+    use proc_macro_error::{clear_emit_hook, set_emit_hook};
+    use std::{cell::RefCell, rc::Rc};
+
+    let observed = Rc::new(RefCell::new(Vec::new()));
+    let observed2 = observed.clone();
+    set_emit_hook(move |diag| observed2.borrow_mut().push(diag.message().to_string()));
+
+    let (_, diagnostics) = with_captured_diagnostics(|| {
+        emit_call_site_warning!("first");
+        emit_call_site_error!("second");
+    });
+    clear_emit_hook();
+
+    with_captured_diagnostics(|| emit_call_site_error!("not observed"));
+
+    assert_eq!(diagnostics.len(), 2);
+    assert_eq!(*observed.borrow(), ["first", "second"]);
+}
+
+#[test]
+fn cancel() {
+    let (_, diagnostics) = with_captured_diagnostics(|| {
+        Diagnostic::new(Level::Error, "false positive".into())
+            .note("some note".into())
+            .cancel();
+    });
+    assert!(diagnostics.is_empty());
+}
+
+#[test]
+fn lazy_notes_of_warnings() {
+    // the warning is promoted to an error after the lazy note is attached
+    let (_, diagnostics) = with_captured_diagnostics(|| {
+        warning!(Span::call_site(), "warning"; note =?? || Some("lazy note"))
+            .with_level(Level::Error)
+            .emit();
+    });
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(
+        diagnostics[0].render(),
+        "error: warning\n  = note: lazy note\n"
+    );
+}
+
+#[test]
+fn eq_ignoring_spans() {
+    use proc_macro2::Span;
+
+    let (_, diagnostics) = with_captured_diagnostics(|| {
+        Diagnostic::new(Level::Error, "error".into())
+            .note("note".into())
+            .span_error(Span::call_site(), "child".into())
+            .emit();
+    });
+
+    let expected = Diagnostic::new(Level::Error, "error".into())
+        .note("note".into())
+        .span_error(Span::call_site(), "child".into());
+    assert!(diagnostics[0].eq_ignoring_spans(&expected));
+
+    let different = Diagnostic::new(Level::Error, "error".into()).help("note".into());
+    assert!(!diagnostics[0].eq_ignoring_spans(&different));
+}
+
+#[test]
+fn reset_state_between_invocations() {
+    reset_state();
+    assert!(cleanup().is_empty());
+
+    let (_, diagnostics) = with_captured_diagnostics(|| emit_call_site_error!("first"));
+    assert_eq!(diagnostics.len(), 1);
+
+    reset_state();
+    let (_, diagnostics) = with_captured_diagnostics(|| emit_call_site_error!("second"));
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].message(), "second");
+}
+
+#[test]
+fn reset_state_ignores_settings() {
+    // the settings of a live entry point stand in for the ones a previous
+    // test could leave behind
+    let settings = EntryPointSettings {
+        summary: true,
+        max_errors: Some(1),
+        ..EntryPointSettings::default()
+    };
+    let output: TokenStream = entry_point(
+        || {
+            emit_call_site_error!("first");
+            emit_call_site_error!("second");
+            reset_state();
+            reset_state();
+            assert!(cleanup().is_empty());
+            quote!(done)
+        },
+        settings,
+    );
+    assert_eq!(output.to_string(), "done");
+
+    let (_, diagnostics) = with_captured_diagnostics(|| emit_call_site_error!("clean"));
+    assert_eq!(diagnostics.len(), 1);
+}
+
+#[test]
+fn emit_and_abort_if() {
+    let (res, diagnostics) = with_captured_diagnostics(|| {
+        Diagnostic::new(Level::Warning, "go on".into()).emit_and_abort_if(false);
+        Diagnostic::new(Level::Error, "stop".into()).emit_and_abort_if(true);
+        Diagnostic::new(Level::Error, "unreachable".into()).emit();
+    });
+
+    assert_eq!(res, None);
+    let messages = diagnostics.iter().map(|d| d.message()).collect::<Vec<_>>();
+    assert_eq!(messages, ["go on", "stop"]);
+}
+
+#[test]
+fn emit_ref() {
+    let (res, diagnostics) = with_captured_diagnostics(|| {
+        let kept = vec![
+            Diagnostic::new(Level::Warning, "first".into()),
+            Diagnostic::new(Level::Error, "second".into()),
+        ];
+        for diag in &kept {
+            diag.emit_ref();
+        }
+        kept
+    });
+
+    let kept = res.unwrap();
+    assert_eq!(diagnostics.len(), kept.len());
+    assert!(diagnostics
+        .iter()
+        .zip(&kept)
+        .all(|(emitted, kept)| emitted.eq_ignoring_spans(kept)));
+}
+
+#[test]
+fn abort_with_level() {
+    let (res, diagnostics) = with_captured_diagnostics(|| {
+        let name = "foo";
+        abort!(
+            Span::call_site(),
+            level = Level::Warning,
+            "`{}` is deprecated", name;
+            note = "stopping anyway"
+        )
+    });
+
+    assert!(res.is_none());
+    let expected = Diagnostic::new(Level::Warning, "`foo` is deprecated".into())
+        .note("stopping anyway".into());
+    assert_eq!(diagnostics.len(), 1);
+    assert!(diagnostics[0].eq_ignoring_spans(&expected));
+}
+
+#[test]
+fn lone_literal_is_formatted() {
+    let name = "foo";
+    let (_, diagnostics) = with_captured_diagnostics(|| {
+        emit_error!(Span::call_site(), "unknown field `{name}`");
+        emit_error!(Span::call_site(), "expected `{{`"; note = "a note");
+        emit_call_site_error!("{name}");
+        emit_call_site_warning!("{name}",);
+    });
+
+    let messages = diagnostics.iter().map(|d| d.message()).collect::<Vec<_>>();
+    assert_eq!(
+        messages,
+        ["unknown field `foo`", "expected `{`", "foo", "foo"]
+    );
+}
+
+#[test]
+fn abort_fn() {
+    let (res, diagnostics) = with_captured_diagnostics(|| {
+        for msg in &["first", "second"] {
+            emit_call_site_error!(msg);
+        }
+        proc_macro_error::abort();
+    });
+
+    assert!(res.is_none());
+    let messages = diagnostics.iter().map(|d| d.message()).collect::<Vec<_>>();
+    assert_eq!(messages, ["first", "second"]);
+}
+
+#[test]
+fn unwrap_or_abort_all() {
+    fn validate(items: &[&str]) -> Result<usize, Vec<Diagnostic>> {
+        let errors = items
+            .iter()
+            .filter(|item| item.is_empty())
+            .map(|_| Diagnostic::new(Level::Error, "empty item".into()))
+            .collect::<Vec<_>>();
+
+        if errors.is_empty() {
+            Ok(items.len())
+        } else {
+            Err(errors)
+        }
+    }
+
+    let (res, diagnostics) =
+        with_captured_diagnostics(|| validate(&["a", "b"]).unwrap_or_abort_all());
+    assert_eq!(res, Some(2));
+    assert!(diagnostics.is_empty());
+
+    let (res, diagnostics) =
+        with_captured_diagnostics(|| validate(&["", "b", ""]).unwrap_or_abort_all());
+    assert_eq!(res, None);
+    let messages = diagnostics.iter().map(|d| d.message()).collect::<Vec<_>>();
+    assert_eq!(messages, ["empty item", "empty item"]);
+}
+
+#[test]
+fn emit_fn() {
+    let (_, diagnostics) = with_captured_diagnostics(|| {
+        vec![
+            Diagnostic::new(Level::Error, "first".into()),
+            Diagnostic::new(Level::Warning, "second".into()),
+        ]
+        .into_iter()
+        .for_each(proc_macro_error::emit);
+    });
+
+    let messages = diagnostics.iter().map(|d| d.message()).collect::<Vec<_>>();
+    assert_eq!(messages, ["first", "second"]);
+}
+
+#[test]
+fn call_site_single_expr() {
+    let (res, diagnostics) = with_captured_diagnostics(|| {
+        let msg = String::from("borrowed message");
+        emit_call_site_error!(msg);
+        emit_call_site_error!(msg);
+
+        let diag = Diagnostic::new(Level::Warning, "diagnostic as is".into());
+        emit_call_site_error!(diag);
+
+        abort_call_site!(Diagnostic::new(Level::Error, "aborted".into()))
+    });
+
+    assert!(res.is_none());
+    let expected = [
+        Diagnostic::new(Level::Error, "borrowed message".into()),
+        Diagnostic::new(Level::Error, "borrowed message".into()),
+        Diagnostic::new(Level::Warning, "diagnostic as is".into()),
+        Diagnostic::new(Level::Error, "aborted".into()),
+    ];
+    assert_eq!(diagnostics.len(), expected.len());
+    for (diag, expected) in diagnostics.iter().zip(&expected) {
+        assert!(diag.eq_ignoring_spans(expected));
+    }
+}
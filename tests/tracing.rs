@@ -0,0 +1,72 @@
+#![cfg(all(feature = "tracing", feature = "testing"))]
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use proc_macro_error::{
+    emit_call_site_error, emit_call_site_warning, testing::with_captured_diagnostics,
+};
+use tracing::{
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+    Event, Level, Metadata, Subscriber,
+};
+
+/// Records the level and the message of every event.
+#[derive(Clone, Default)]
+struct Collector(Arc<Mutex<Vec<(Level, String)>>>);
+
+struct MessageVisitor(String);
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.0 = format!("{:?}", value);
+        }
+    }
+}
+
+impl Subscriber for Collector {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, _: &Attributes<'_>) -> Id {
+        Id::from_u64(1)
+    }
+
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut visitor = MessageVisitor(String::new());
+        event.record(&mut visitor);
+        let level = *event.metadata().level();
+        self.0.lock().unwrap().push((level, visitor.0));
+    }
+
+    fn enter(&self, _: &Id) {}
+
+    fn exit(&self, _: &Id) {}
+}
+
+#[test]
+fn emitted_diagnostics_are_traced() {
+    let collector = Collector::default();
+    tracing::subscriber::with_default(collector.clone(), || {
+        with_captured_diagnostics(|| {
+            emit_call_site_warning!("a warning");
+            emit_call_site_error!("an error");
+        });
+    });
+
+    let events = collector.0.lock().unwrap();
+    assert_eq!(
+        *events,
+        [
+            (Level::WARN, "a warning".to_string()),
+            (Level::ERROR, "an error".to_string()),
+        ]
+    );
+}
@@ -0,0 +1,8 @@
+extern crate test_crate;
+use test_crate::*;
+
+append_dummy_order!(order);
+
+fn main() {
+    let _ = ORDER;
+}
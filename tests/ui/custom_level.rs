@@ -0,0 +1,6 @@
+extern crate test_crate;
+use test_crate::*;
+
+custom_level!(one);
+
+fn main() {}
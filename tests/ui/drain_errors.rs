@@ -0,0 +1,8 @@
+extern crate test_crate;
+use test_crate::*;
+
+drain_errors!(one two);
+
+fn main() {
+    generated();
+}
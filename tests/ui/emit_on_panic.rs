@@ -0,0 +1,6 @@
+extern crate test_crate;
+use test_crate::*;
+
+emit_on_panic!(one);
+
+fn main() {}
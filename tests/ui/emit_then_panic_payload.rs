@@ -0,0 +1,7 @@
+extern crate test_crate;
+use test_crate::*;
+
+emit_then_panic_string!(one);
+emit_then_panic_payload!(two);
+
+fn main() {}
@@ -0,0 +1,6 @@
+extern crate test_crate;
+use test_crate::*;
+
+error_code!(one, two);
+
+fn main() {}
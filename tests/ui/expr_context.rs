@@ -0,0 +1,6 @@
+extern crate test_crate;
+use test_crate::*;
+
+fn main() {
+    let _x: u32 = expr_context!(one, two);
+}
@@ -0,0 +1,6 @@
+extern crate test_crate;
+use test_crate::*;
+
+fallback_span!();
+
+fn main() {}
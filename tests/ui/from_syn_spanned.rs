@@ -0,0 +1,6 @@
+extern crate test_crate;
+use test_crate::*;
+
+from_syn_spanned!(one two three);
+
+fn main() {}
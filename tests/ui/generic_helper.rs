@@ -0,0 +1,6 @@
+extern crate test_crate;
+use test_crate::*;
+
+generic_helper!(one two);
+
+fn main() {}
@@ -0,0 +1,6 @@
+extern crate test_crate;
+use test_crate::*;
+
+into_syn_error!(one, two);
+
+fn main() {}
@@ -0,0 +1,7 @@
+extern crate test_crate;
+use test_crate::*;
+
+max_errors!(one two three four);
+max_errors!(five six seven);
+
+fn main() {}
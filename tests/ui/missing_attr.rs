@@ -0,0 +1,6 @@
+extern crate test_crate;
+use test_crate::*;
+
+missing_attr!(this macro forgot the attribute, and its invocation is rather long);
+
+fn main() {}
@@ -0,0 +1,6 @@
+extern crate test_crate;
+use test_crate::*;
+
+nested_entry_point!(one Two three);
+
+fn main() {}
@@ -2,5 +2,7 @@ extern crate test_crate;
 use test_crate::*;
 
 option_ext!(one, two);
+option_unwrap_or_abort!(one, two);
+option_ok_or_abort!(one, two);
 
 fn main() {}
@@ -0,0 +1,9 @@
+extern crate test_crate;
+use test_crate::*;
+
+proc_macro2_helper!(Fine);
+proc_macro2_helper!(NotFine extra);
+
+fn main() {
+    let _ = Fine;
+}
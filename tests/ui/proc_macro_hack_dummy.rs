@@ -0,0 +1,10 @@
+// Adapted from https://github.com/dtolnay/proc-macro-hack/blob/master/example/src/main.rs
+// Licensed under either of Apache License, Version 2.0 or MIT license at your option.
+
+use proc_macro_hack_test::add_one_dummy;
+
+fn main() {
+    let two = 2;
+    let nine = add_one_dummy!(two) + add_one_dummy!(2 + 3);
+    println!("nine = {}", nine);
+}
@@ -0,0 +1,5 @@
+use proc_macro_hack_test::add_one_qualified;
+
+fn main() {
+    let _ = add_one_qualified!(2 + 3);
+}
@@ -0,0 +1,6 @@
+extern crate test_crate;
+use test_crate::*;
+
+proc_macro_span!(one);
+
+fn main() {}
@@ -0,0 +1,14 @@
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro_error::proc_macro_error;
+
+// `#[proc_macro_error]` must not complain about the attribute being used
+// outside of a proc-macro: the path ends with `proc_macro`.
+#[proc_macro_error]
+#[self::proc_macro]
+pub fn qualified(input: TokenStream) -> TokenStream {
+    input
+}
+
+fn main() {}
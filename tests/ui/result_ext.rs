@@ -3,5 +3,7 @@ use test_crate::*;
 
 result_unwrap_or_abort!(one, two);
 result_expect_or_abort!(one, two);
+result_emit_or!(one, two);
+result_map_err_to_diagnostic!(one);
 
 fn main() {}
@@ -0,0 +1,6 @@
+extern crate test_crate;
+use test_crate::*;
+
+source_context!(r#"raw string"#);
+
+fn main() {}
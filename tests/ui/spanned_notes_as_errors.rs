@@ -0,0 +1,6 @@
+extern crate test_crate;
+use test_crate::*;
+
+spanned_notes_as_errors!(one, two);
+
+fn main() {}
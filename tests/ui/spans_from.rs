@@ -0,0 +1,6 @@
+extern crate test_crate;
+use test_crate::*;
+
+spans_from!(Vec<Option<u8>>);
+
+fn main() {}
@@ -0,0 +1,7 @@
+extern crate test_crate;
+use test_crate::*;
+
+summary!(one two three);
+summary!(single);
+
+fn main() {}
@@ -0,0 +1,6 @@
+extern crate test_crate;
+use test_crate::*;
+
+warn_as_error!(one, two, three);
+
+fn main() {}
@@ -0,0 +1,6 @@
+extern crate test_crate;
+use test_crate::*;
+
+warning_count!(one two);
+
+fn main() {}